@@ -1,72 +1,375 @@
-use std::collections::BTreeSet;
+mod merkle;
+mod vesting;
+
+use merkle::LeafPayload;
+use std::collections::BTreeMap;
 use tari_template_lib::prelude::*;
+use tari_template_lib::types::Hash32;
+use vesting::{VestingPosition, VestingSchedule};
+
+// Recipients are added while the airdrop is a draft, and can claim while it is open
+#[derive(Clone, Copy, Debug, PartialEq, Eq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+#[cbor(index_only)]
+pub enum AirdropStatus {
+    #[n(0)]
+    Draft,
+    #[n(1)]
+    Open,
+    #[n(2)]
+    Closed,
+}
 
 #[template]
 pub mod {{ project-name | snake_case }} {
     use super::*;
 
     pub struct {{ project-name | upper_camel_case }} {
-        allow_list: BTreeSet<ComponentAddress>,
-        is_airdrop_open: bool,
+        // amount of tokens for each recipient
+        allow_list: BTreeMap<ComponentAddress, Amount>,
+        status: AirdropStatus,
         claimed_count: u64,
         vault: Vault,
+        // root of the tree of recipients when the airdrop uses Merkle proofs instead of the allow list
+        merkle_root: Option<Hash32>,
+        // bitmap of the claimed leaves of the Merkle tree
+        claimed_leaves: Vec<u64>,
+        // tokens can be claimed from the start epoch until before the end epoch, after which the owner can reclaim the
+        // tokens that have not been claimed
+        start_epoch: u64,
+        end_epoch: u64,
+        // vesting applied to claims, which mint a vesting position instead of depositing the tokens
+        vesting_schedule: Option<VestingSchedule>,
+        position_resource: Option<ResourceAddress>,
+        // tokens of each vesting position that have not been released
+        position_vaults: BTreeMap<NonFungibleId, Vault>,
     }
 
     impl {{ project-name | upper_camel_case }} {
-        pub fn new() -> Component<Self> {
+        pub fn new(start_epoch: u64, end_epoch: u64) -> Component<Self> {
+            Self::validate_claim_window(start_epoch, end_epoch);
             let bucket = ResourceBuilder::non_fungible()
                 .with_token_symbol("AIR")
                 .with_owner_rule(OwnerRule::OwnedBySigner)
                 .initial_supply((1..=100).map(NonFungibleId::from_u32));
 
+            Component::new(Self::draft(Vault::from_bucket(bucket), start_epoch, end_epoch))
+                .with_access_rules(Self::access_rules())
+                .create()
+        }
+
+        /// Creates an airdrop of the fungible tokens in the bucket, which the owner can reclaim if they are not claimed
+        /// before the end epoch
+        pub fn new_fungible(tokens: Bucket, start_epoch: u64, end_epoch: u64) -> Component<Self> {
+            assert!(
+                tokens.resource_type() == ResourceType::Fungible,
+                "Airdrop tokens must be fungible"
+            );
+            Self::validate_claim_window(start_epoch, end_epoch);
+
+            Component::new(Self::draft(Vault::from_bucket(tokens), start_epoch, end_epoch))
+                .with_access_rules(Self::access_rules())
+                .create()
+        }
+
+        /// Creates an airdrop of the fungible tokens in the bucket where each claim mints a non-fungible vesting
+        /// position. The tokens of a position unlock linearly over the duration from the claim, but nothing can be
+        /// released before the cliff
+        pub fn new_vesting(
+            tokens: Bucket,
+            start_epoch: u64,
+            end_epoch: u64,
+            cliff_epochs: u64,
+            duration_epochs: u64,
+        ) -> Component<Self> {
+            assert!(
+                tokens.resource_type() == ResourceType::Fungible,
+                "Airdrop tokens must be fungible"
+            );
+            Self::validate_claim_window(start_epoch, end_epoch);
+            let vesting_schedule = VestingSchedule {
+                cliff_epochs,
+                duration_epochs,
+            };
+            vesting_schedule.validate();
+
+            let component_alloc = CallerContext::allocate_component_address(None);
+            let position_resource = ResourceBuilder::non_fungible()
+                .with_token_symbol("VEST")
+                .with_owner_rule(OwnerRule::OwnedBySigner)
+                .mintable(rule!(component(component_alloc.get_address())), LOCKED)
+                .build();
+
             Component::new(Self {
-                allow_list: BTreeSet::new(),
-                is_airdrop_open: false,
-                claimed_count: 0,
-                vault: Vault::from_bucket(bucket),
+                vesting_schedule: Some(vesting_schedule),
+                position_resource: Some(position_resource),
+                ..Self::draft(Vault::from_bucket(tokens), start_epoch, end_epoch)
+            })
+                .with_address_allocation(component_alloc)
+                .with_access_rules(Self::access_rules())
+                .create()
+        }
+
+        /// Creates an airdrop for any number of recipients, which are the leaves of a Merkle tree with the root. The
+        /// tokens are minted when they are claimed, so each leaf must have the id of a non-fungible token
+        pub fn new_with_merkle_root(merkle_root: Hash32, start_epoch: u64, end_epoch: u64) -> Component<Self> {
+            Self::validate_claim_window(start_epoch, end_epoch);
+            let component_alloc = CallerContext::allocate_component_address(None);
+            let resource = ResourceBuilder::non_fungible()
+                .with_token_symbol("AIR")
+                .with_owner_rule(OwnerRule::OwnedBySigner)
+                .mintable(rule!(component(component_alloc.get_address())), LOCKED)
+                .build();
+
+            Component::new(Self {
+                merkle_root: Some(merkle_root),
+                ..Self::draft(Vault::new_empty(resource), start_epoch, end_epoch)
+            })
+                .with_address_allocation(component_alloc)
+                .with_access_rules(Self::access_rules())
+                .create()
+        }
+
+        /// Creates an airdrop of the fungible tokens in the bucket for any number of recipients, which are the leaves
+        /// of a Merkle tree with the root. Each leaf must have the amount of tokens of the recipient
+        pub fn new_fungible_with_merkle_root(
+            tokens: Bucket,
+            merkle_root: Hash32,
+            start_epoch: u64,
+            end_epoch: u64,
+        ) -> Component<Self> {
+            assert!(
+                tokens.resource_type() == ResourceType::Fungible,
+                "Airdrop tokens must be fungible"
+            );
+            Self::validate_claim_window(start_epoch, end_epoch);
+
+            Component::new(Self {
+                merkle_root: Some(merkle_root),
+                ..Self::draft(Vault::from_bucket(tokens), start_epoch, end_epoch)
             })
-                .with_access_rules(AccessRules::allow_all())
+                .with_access_rules(Self::access_rules())
                 .create()
         }
 
-        pub fn add_recipient(&mut self, address: ComponentAddress) {
-            assert!(self.is_airdrop_open, "Airdrop already started");
+        /// Adds a recipient of an amount of tokens, which is the number of tokens for non-fungible airdrops
+        pub fn add_recipient(&mut self, address: ComponentAddress, amount: Amount) {
+            assert!(self.merkle_root.is_none(), "Airdrop recipients are in a Merkle tree");
+            assert!(self.status == AirdropStatus::Draft, "Airdrop already started");
             assert!(self.allow_list.len() < 100, "Airdrop allow list is full");
             assert!(
-                !self.allow_list.contains(&address),
+                !self.allow_list.contains_key(&address),
                 "Address already in allow list"
             );
-            self.allow_list.insert(address);
+            assert!(amount.is_positive(), "Airdrop amount must be positive");
+            let allocated = self.allow_list.values().fold(amount, |total, amount| total + *amount);
+            assert!(
+                allocated <= self.vault.balance(),
+                "Airdrop does not have enough tokens for the recipient"
+            );
+            self.allow_list.insert(address, amount);
         }
 
         pub fn open_airdrop(&mut self) {
-            assert!(!self.is_airdrop_open, "Airdrop already open");
-            self.is_airdrop_open = true;
+            match self.status {
+                AirdropStatus::Draft => self.status = AirdropStatus::Open,
+                AirdropStatus::Open => panic!("Airdrop already open"),
+                AirdropStatus::Closed => panic!("Airdrop is closed"),
+            }
+        }
+
+        /// Ends the airdrop, after which no more tokens can be claimed
+        pub fn close_airdrop(&mut self) {
+            self.assert_open();
+            self.status = AirdropStatus::Closed;
+        }
+
+        pub fn status(&self) -> AirdropStatus {
+            self.status
+        }
+
+        /// Deposits the tokens of the address into its account, or a vesting position for the tokens if the airdrop
+        /// vests
+        pub fn claim_any(&mut self, address: ComponentAddress) {
+            self.assert_claimable();
+            let amount = self.allow_list.remove(&address).unwrap_or_else(|| {
+                panic!(
+                    "Address {} is not in allow list or has already been claimed",
+                    address
+                )
+            });
+
+            self.claimed_count += 1;
+            let tokens = self.vault.withdraw(amount);
+            let bucket = match self.vesting_schedule {
+                Some(schedule) => self.mint_vesting_position(tokens, schedule),
+                None => tokens,
+            };
+            ComponentManager::get(address).invoke("deposit", args![bucket]);
+        }
+
+        /// Deposits the non-fungible token with the id into the account of the address
+        pub fn claim_specific(&mut self, address: ComponentAddress, id: NonFungibleId) {
+            self.assert_claimable();
+            let amount = self.allow_list.remove(&address).unwrap_or_else(|| {
+                panic!(
+                    "Address {} is not in allow list or has already been claimed",
+                    address
+                )
+            });
+            assert!(amount == 1, "Address {} must claim {} tokens", address, amount);
+
+            self.claimed_count += 1;
+            let bucket = self.vault.withdraw_non_fungibles(Some(id));
+            ComponentManager::get(address).invoke("deposit", args![bucket]);
         }
 
-        pub fn claim_any(&mut self, address: ComponentAddress) -> Bucket {
-            assert!(self.is_airdrop_open, "Airdrop is not open");
-            // Note: this does not enforce that the token is deposited in an address from the allow list
+        /// Claims the tokens of a leaf of the Merkle tree, which are deposited into the account of the leaf
+        pub fn claim(
+            &mut self,
+            address: ComponentAddress,
+            leaf_data: (u64, LeafPayload),
+            proof: Vec<Hash32>,
+        ) {
+            self.assert_claimable();
+            let merkle_root = self
+                .merkle_root
+                .expect("Airdrop does not have a Merkle root");
+            let (index, payload) = leaf_data;
             assert!(
-                self.allow_list.remove(&address),
-                "Address {} is not in allow list or has already been claimed",
-                address
+                !merkle::is_claimed(&self.claimed_leaves, index),
+                "Leaf {} has already been claimed",
+                index
+            );
+            let leaf = merkle::leaf_hash(index, &address, &payload);
+            assert!(
+                merkle::verify_proof(&merkle_root, leaf, &proof),
+                "Invalid Merkle proof"
             );
 
+            merkle::set_claimed(&mut self.claimed_leaves, index);
             self.claimed_count += 1;
-            self.vault.withdraw(1u64)
+            let resource_manager = self.vault.get_resource_manager();
+            let bucket = match payload {
+                LeafPayload::Amount(amount) => {
+                    assert!(
+                        resource_manager.resource_type() == ResourceType::Fungible,
+                        "Airdrop leaves must have non-fungible ids"
+                    );
+                    self.vault.withdraw(amount)
+                }
+                LeafPayload::NonFungible(id) => {
+                    assert!(
+                        resource_manager.resource_type() == ResourceType::NonFungible,
+                        "Airdrop leaves must have amounts"
+                    );
+                    resource_manager.mint_non_fungible(id, &(), &())
+                }
+            };
+            ComponentManager::get(address).invoke("deposit", args![bucket]);
+        }
+
+        pub fn is_claimed(&self, index: u64) -> bool {
+            merkle::is_claimed(&self.claimed_leaves, index)
         }
 
-        pub fn claim_specific(&mut self, address: ComponentAddress, id: NonFungibleId) -> Bucket {
-            assert!(self.is_airdrop_open, "Airdrop is not open");
+        /// Withdraws the tokens of the vesting position in the proof that have unlocked since they were last released
+        pub fn release(&mut self, position_proof: Proof) -> Bucket {
+            let position_resource = self
+                .position_resource
+                .expect("Airdrop does not vest");
+            position_proof.assert_resource(position_resource);
+            let positions = position_proof.get_non_fungibles();
+            assert_eq!(
+                positions.len(),
+                1,
+                "The proof must contain exactly one vesting position"
+            );
+            let id = positions.into_iter().next().unwrap();
+
+            let position = ResourceManager::get(position_resource)
+                .get_non_fungible(&id)
+                .get_data::<VestingPosition>();
+            let current_epoch = Consensus::current_epoch();
             assert!(
-                self.allow_list.remove(&address),
-                "Address {} is not in allow list or has already been claimed",
-                address
+                current_epoch >= position.cliff_epoch(),
+                "Vesting position {} is locked until epoch {}",
+                id,
+                position.cliff_epoch()
+            );
+            let vault = self
+                .position_vaults
+                .get_mut(&id)
+                .expect("Vesting position vault not found");
+            let released = position.total - vault.balance();
+            let amount = position.unlocked_amount(current_epoch) - released;
+            assert!(
+                amount.is_positive(),
+                "No tokens of vesting position {} have unlocked since the last release",
+                id
             );
 
-            self.claimed_count += 1;
-            self.vault.withdraw_non_fungibles(Some(id))
+            emit_event(
+                "release",
+                [
+                    ("position", id.to_string()),
+                    ("amount", amount.to_string()),
+                    ("released", (released + amount).to_string()),
+                    ("total", position.total.to_string()),
+                ],
+            );
+            vault.withdraw(amount)
+        }
+
+        /// Returns the amount of tokens of the vesting position that can be released in the current epoch
+        pub fn releasable_amount(&self, position_id: NonFungibleId) -> Amount {
+            let position_resource = self
+                .position_resource
+                .expect("Airdrop does not vest");
+            let position = ResourceManager::get(position_resource)
+                .get_non_fungible(&position_id)
+                .get_data::<VestingPosition>();
+            let vault = self
+                .position_vaults
+                .get(&position_id)
+                .expect("Vesting position vault not found");
+            let released = position.total - vault.balance();
+            position.unlocked_amount(Consensus::current_epoch()) - released
+        }
+
+        /// Returns the tokens that have not been claimed by the end epoch, and closes the airdrop
+        pub fn reclaim_unclaimed(&mut self) -> Bucket {
+            assert!(
+                Consensus::current_epoch() >= self.end_epoch,
+                "Unclaimed tokens cannot be reclaimed before epoch {}",
+                self.end_epoch
+            );
+
+            self.close_and_withdraw_unclaimed()
+        }
+
+        /// Closes the airdrop at any time and deposits the tokens that have not been claimed into the treasury account
+        pub fn close_and_sweep(&mut self, treasury: ComponentAddress) {
+            let bucket = self.close_and_withdraw_unclaimed();
+            // the amount of unclaimed tokens, which is the number of tokens for non-fungible airdrops
+            let unclaimed = bucket.amount();
+            emit_event(
+                "close_and_sweep",
+                [
+                    ("claimed", self.claimed_count.to_string()),
+                    ("unclaimed", unclaimed.to_string()),
+                    ("treasury", treasury.to_string()),
+                ],
+            );
+
+            if unclaimed.is_positive() {
+                ComponentManager::get(treasury).invoke("deposit", args![bucket]);
+            } else {
+                bucket.drop_empty();
+            }
+        }
+
+        pub fn claim_window(&self) -> (u64, u64) {
+            (self.start_epoch, self.end_epoch)
         }
 
         pub fn total_supply(&self) -> Amount {
@@ -86,5 +389,99 @@ pub mod {{ project-name | snake_case }} {
                 .get_resource_manager()
                 .update_access_rule(action, new_rule)
         }
+
+        // a draft airdrop of the tokens in the vault, without vesting or a Merkle root
+        fn draft(vault: Vault, start_epoch: u64, end_epoch: u64) -> Self {
+            Self {
+                allow_list: BTreeMap::new(),
+                status: AirdropStatus::Draft,
+                claimed_count: 0,
+                vault,
+                merkle_root: None,
+                claimed_leaves: Vec::new(),
+                start_epoch,
+                end_epoch,
+                vesting_schedule: None,
+                position_resource: None,
+                position_vaults: BTreeMap::new(),
+            }
+        }
+
+        // claims and views are public, the other methods can only be called by the owner
+        fn access_rules() -> AccessRules {
+            AccessRules::new()
+                .add_method_rule("status", AccessRule::AllowAll)
+                .add_method_rule("claim_window", AccessRule::AllowAll)
+                .add_method_rule("claim_any", AccessRule::AllowAll)
+                .add_method_rule("claim_specific", AccessRule::AllowAll)
+                .add_method_rule("claim", AccessRule::AllowAll)
+                .add_method_rule("is_claimed", AccessRule::AllowAll)
+                .add_method_rule("release", AccessRule::AllowAll)
+                .add_method_rule("releasable_amount", AccessRule::AllowAll)
+                .add_method_rule("total_supply", AccessRule::AllowAll)
+                .add_method_rule("num_claimed", AccessRule::AllowAll)
+                .add_method_rule("vault_balance", AccessRule::AllowAll)
+                .default(AccessRule::DenyAll)
+        }
+
+        // moves the claimed tokens into the vault of a new vesting position, and returns the position
+        fn mint_vesting_position(&mut self, tokens: Bucket, schedule: VestingSchedule) -> Bucket {
+            let position_resource = self
+                .position_resource
+                .expect("Airdrop does not vest");
+            let id = NonFungibleId::from_u64(self.claimed_count);
+            let position = VestingPosition::new(tokens.amount(), Consensus::current_epoch(), schedule);
+            emit_event(
+                "vesting_position",
+                [
+                    ("position", id.to_string()),
+                    ("total", position.total.to_string()),
+                    ("start_epoch", position.start_epoch.to_string()),
+                    ("cliff_epochs", position.cliff_epochs.to_string()),
+                    ("duration_epochs", position.duration_epochs.to_string()),
+                ],
+            );
+
+            self.position_vaults
+                .insert(id.clone(), Vault::from_bucket(tokens));
+            ResourceManager::get(position_resource).mint_non_fungible(id, &position, &())
+        }
+
+        // unclaimed tokens are withdrawn by both reclaiming and sweeping, after which nobody can claim
+        fn close_and_withdraw_unclaimed(&mut self) -> Bucket {
+            self.allow_list.clear();
+            self.status = AirdropStatus::Closed;
+            self.vault.withdraw_all()
+        }
+
+        fn assert_open(&self) {
+            assert!(self.status == AirdropStatus::Open, "Airdrop is not open");
+        }
+
+        fn assert_claimable(&self) {
+            self.assert_open();
+            let current_epoch = Consensus::current_epoch();
+            assert!(
+                current_epoch >= self.start_epoch,
+                "Airdrop claims start at epoch {}",
+                self.start_epoch
+            );
+            assert!(
+                current_epoch < self.end_epoch,
+                "Airdrop claims ended at epoch {}",
+                self.end_epoch
+            );
+        }
+
+        fn validate_claim_window(start_epoch: u64, end_epoch: u64) {
+            assert!(
+                end_epoch > start_epoch,
+                "Airdrop claim window must end after it starts"
+            );
+            assert!(
+                end_epoch > Consensus::current_epoch(),
+                "Airdrop claim window must end in the future"
+            );
+        }
     }
 }
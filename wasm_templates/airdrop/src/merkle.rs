@@ -0,0 +1,64 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+// Merkle proofs for airdrops to large numbers of recipients, where the component only stores the root of a tree of
+// (index, account, amount or non-fungible id) leaves. Pairs of nodes are hashed in sorted order, so proofs don't need
+// to say on which side each sibling is, and leaves and nodes are hashed with different prefixes so a node cannot be
+// used as a leaf
+
+use sha2::{Digest, Sha256};
+use tari_template_lib::prelude::*;
+use tari_template_lib::types::Hash32;
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+// What the recipient of a leaf receives, an amount of fungible tokens or a non-fungible token with the id
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub enum LeafPayload {
+    #[n(0)]
+    Amount(#[n(0)] Amount),
+    #[n(1)]
+    NonFungible(#[n(0)] NonFungibleId),
+}
+
+// hash of the CBOR encoding of "(index, account, payload)"
+pub fn leaf_hash(index: u64, account: &ComponentAddress, payload: &LeafPayload) -> Hash32 {
+    let data = tari_bor::encode(&(index, account, payload)).expect("failed to encode airdrop leaf");
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    Hash32::from_array(hasher.finalize().into())
+}
+
+pub fn node_hash(a: &Hash32, b: &Hash32) -> Hash32 {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    Hash32::from_array(hasher.finalize().into())
+}
+
+pub fn verify_proof(root: &Hash32, leaf: Hash32, proof: &[Hash32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| node_hash(&node, sibling));
+    computed == *root
+}
+
+// The claimed leaves are a bitmap, with one bit for each leaf index
+pub fn is_claimed(bitmap: &[u64], index: u64) -> bool {
+    let (word, bit) = bitmap_position(index);
+    bitmap.get(word).is_some_and(|w| w & (1 << bit) != 0)
+}
+
+pub fn set_claimed(bitmap: &mut Vec<u64>, index: u64) {
+    let (word, bit) = bitmap_position(index);
+    if bitmap.len() <= word {
+        bitmap.resize(word + 1, 0);
+    }
+    bitmap[word] |= 1 << bit;
+}
+
+fn bitmap_position(index: u64) -> (usize, u64) {
+    ((index / 64) as usize, index % 64)
+}
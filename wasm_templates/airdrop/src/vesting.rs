@@ -0,0 +1,63 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+use tari_template_lib::types::Amount;
+
+// Vesting applied to every claim of a vesting airdrop, in epochs from the claim
+#[derive(Clone, Copy, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct VestingSchedule {
+    #[n(0)]
+    pub cliff_epochs: u64,
+    #[n(1)]
+    pub duration_epochs: u64,
+}
+
+impl VestingSchedule {
+    pub fn validate(&self) {
+        assert!(self.duration_epochs > 0, "Vesting duration must be positive");
+        assert!(
+            self.cliff_epochs <= self.duration_epochs,
+            "Vesting cliff cannot be longer than the vesting duration"
+        );
+    }
+}
+
+// Data of the non-fungible vesting position minted for each claim. Nothing unlocks before the cliff, after which the
+// tokens unlock linearly from the start epoch until the end of the duration
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct VestingPosition {
+    #[n(0)]
+    pub total: Amount,
+    #[n(1)]
+    pub start_epoch: u64,
+    #[n(2)]
+    pub cliff_epochs: u64,
+    #[n(3)]
+    pub duration_epochs: u64,
+}
+
+impl VestingPosition {
+    pub fn new(total: Amount, start_epoch: u64, schedule: VestingSchedule) -> Self {
+        Self {
+            total,
+            start_epoch,
+            cliff_epochs: schedule.cliff_epochs,
+            duration_epochs: schedule.duration_epochs,
+        }
+    }
+
+    pub fn cliff_epoch(&self) -> u64 {
+        self.start_epoch + self.cliff_epochs
+    }
+
+    pub fn unlocked_amount(&self, current_epoch: u64) -> Amount {
+        let elapsed = current_epoch.saturating_sub(self.start_epoch);
+        if elapsed < self.cliff_epochs {
+            return Amount::zero();
+        }
+        if elapsed >= self.duration_epochs {
+            return self.total;
+        }
+        self.total * elapsed / self.duration_epochs
+    }
+}
@@ -0,0 +1,17 @@
+use tari_template_lib::prelude::*;
+
+// Helper template used by the tests to create the fungible tokens of airdrops
+#[template]
+mod test_tokens {
+    use super::*;
+
+    pub struct TestTokens {}
+
+    impl TestTokens {
+        pub fn create(symbol: String, initial_supply: Amount) -> Bucket {
+            ResourceBuilder::public_fungible()
+                .with_token_symbol(symbol)
+                .initial_supply(initial_supply)
+        }
+    }
+}
@@ -1,25 +1,56 @@
 use tari_template_test_tooling::transaction::{Transaction, args};
 use tari_template_test_tooling::support::assert_error::assert_reject_reason;
 use tari_template_lib::types::NonFungibleAddress;
-use tari_template_lib::prelude::{Amount, ComponentAddress};
+use tari_template_lib::prelude::{
+    tari_bor, AccessRule, Amount, ComponentAddress, NonFungibleId, ResourceAuthAction,
+};
+use tari_template_lib::types::Hash32;
+use sha2::{Digest, Sha256};
+use tari_template_test_tooling::engine_types::virtual_substate::{VirtualSubstate, VirtualSubstateId};
 use tari_template_test_tooling::crypto::RistrettoSecretKey;
 use tari_template_test_tooling::TemplateTest;
+use tari_template_test_tooling::engine_types::commit_result::ExecuteResult;
+
+// mirrors the status enum of the template, which is not a library dependency of the tests
+#[derive(Debug, PartialEq, Eq, minicbor::Decode)]
+#[cbor(index_only)]
+enum AirdropStatus {
+    #[n(0)]
+    _Draft,
+    #[n(1)]
+    _Open,
+    #[n(2)]
+    Closed,
+}
+
+// mirrors the payload of the Merkle leaves of the template
+#[derive(Debug, Clone, minicbor::Encode, minicbor::CborLen)]
+enum LeafPayload {
+    #[n(0)]
+    Amount(#[n(0)] Amount),
+    #[n(1)]
+    NonFungible(#[n(0)] NonFungibleId),
+}
 
 struct AirdropResult {
-    _account_address: ComponentAddress,
-    _account_proof: NonFungibleAddress,
-    _account_secret: RistrettoSecretKey,
+    _owner_address: ComponentAddress,
+    owner_proof: NonFungibleAddress,
+    owner_secret: RistrettoSecretKey,
     airdrop_address: ComponentAddress,
 }
 
 fn airdrop(test: &mut TemplateTest) -> AirdropResult {
+    airdrop_with_claim_window(test, 0, 100)
+}
+
+fn airdrop_with_claim_window(test: &mut TemplateTest, start_epoch: u64, end_epoch: u64) -> AirdropResult {
     let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
     let result = test.execute_expect_success(
         Transaction::builder_localnet()
             .call_function(
                 test.get_template_address("{{ project-name | upper_camel_case }}"),
                 "new",
-                args![],
+                args![start_epoch, end_epoch],
             )
             .build_and_seal(&account_secret_key),
         vec![owner_proof.clone()],
@@ -30,9 +61,9 @@ fn airdrop(test: &mut TemplateTest) -> AirdropResult {
         .unwrap();
 
     AirdropResult {
-        _account_address: account_component,
-        _account_proof: owner_proof,
-        _account_secret: account_secret_key,
+        _owner_address: account_component,
+        owner_proof,
+        owner_secret: account_secret_key,
         airdrop_address,
     }
 }
@@ -42,17 +73,22 @@ fn test_airdrop_add_recipient_airdrop_already_started() {
     let mut test = TemplateTest::my_crate();
     let airdrop_result = airdrop(&mut test);
 
-    let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+    let (account_component, _, _) = test.create_funded_account();
 
     let result = test.execute_expect_failure(
         Transaction::builder_localnet()
+            .call_method(
+                airdrop_result.airdrop_address,
+                "open_airdrop",
+                args![],
+            )
             .call_method(
                 airdrop_result.airdrop_address,
                 "add_recipient",
-                args![account_component],
+                args![account_component, 1],
             )
-            .build_and_seal(&account_secret_key),
-        vec![owner_proof.clone()],
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
     );
 
     assert_reject_reason(result, "Airdrop already started");
@@ -63,47 +99,33 @@ fn test_airdrop_add_recipient_airdrop_allow_list_full() {
     let mut test = TemplateTest::my_crate();
     let airdrop_result = airdrop(&mut test);
 
-    // open airdrop
-    let (_account_component, owner_proof, account_secret_key) = test.create_funded_account();
-    let result = test.execute_expect_success(
-        Transaction::builder_localnet()
-            .call_method(
-                airdrop_result.airdrop_address,
-                "open_airdrop",
-                args![],
-            )
-            .build_and_seal(&account_secret_key),
-        vec![owner_proof.clone()],
-    );
-    assert!(result.finalize.result.is_accept());
-
     // add recipients
     for _ in 0..100 {
-        let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+        let (account_component, _, _) = test.create_funded_account();
         let result = test.execute_expect_success(
             Transaction::builder_localnet()
                 .call_method(
                     airdrop_result.airdrop_address,
                     "add_recipient",
-                    args![account_component],
+                    args![account_component, 1],
                 )
-                .build_and_seal(&account_secret_key),
-            vec![owner_proof.clone()],
+                .build_and_seal(&airdrop_result.owner_secret),
+            vec![airdrop_result.owner_proof.clone()],
         );
         assert!(result.finalize.result.is_accept());
     }
 
     // fail to add more recipient than allowed
-    let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+    let (account_component, _, _) = test.create_funded_account();
     let result = test.execute_expect_failure(
         Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
                 "add_recipient",
-                args![account_component],
+                args![account_component, 1],
             )
-            .build_and_seal(&account_secret_key),
-        vec![owner_proof.clone()],
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
     );
 
     assert_reject_reason(result, "Airdrop allow list is full");
@@ -114,22 +136,22 @@ fn test_airdrop_add_recipient_success() {
     let mut test = TemplateTest::my_crate();
     let airdrop_result = airdrop(&mut test);
 
-    // open airdrop
-    let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+    // add recipient and open airdrop
+    let (account_component, _, _) = test.create_funded_account();
     let result = test.execute_expect_success(
         Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
-                "open_airdrop",
-                args![],
+                "add_recipient",
+                args![account_component, 1],
             )
             .call_method(
                 airdrop_result.airdrop_address,
-                "add_recipient",
-                args![account_component],
+                "open_airdrop",
+                args![],
             )
-            .build_and_seal(&account_secret_key),
-        vec![owner_proof.clone()],
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
     );
     assert!(result.finalize.result.is_accept());
 }
@@ -140,7 +162,6 @@ fn test_airdrop_open_airdrop_failure() {
     let airdrop_result = airdrop(&mut test);
 
     // open airdrop
-    let (_account_component, owner_proof, account_secret_key) = test.create_funded_account();
     let result = test.execute_expect_failure(
         Transaction::builder_localnet()
             .call_method(
@@ -153,12 +174,103 @@ fn test_airdrop_open_airdrop_failure() {
                 "open_airdrop",
                 args![],
             )
-            .build_and_seal(&account_secret_key),
-        vec![owner_proof.clone()],
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
     );
     assert_reject_reason(result, "Airdrop already open");
 }
 
+#[test]
+fn test_airdrop_admin_methods_are_owner_only() {
+    let mut test = TemplateTest::my_crate();
+    let airdrop_result = airdrop(&mut test);
+    let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+
+    for (method, args) in [
+        ("add_recipient", args![account_component, 1]),
+        ("open_airdrop", args![]),
+        ("close_airdrop", args![]),
+        ("close_and_sweep", args![account_component]),
+        ("reclaim_unclaimed", args![]),
+        (
+            "update_access_rule",
+            args![ResourceAuthAction::Withdraw, AccessRule::AllowAll],
+        ),
+    ] {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_method(airdrop_result.airdrop_address, method, args)
+                .build_and_seal(&account_secret_key),
+            vec![owner_proof.clone()],
+        );
+        assert_reject_reason(reason, "Access Denied");
+    }
+}
+
+#[test]
+fn test_airdrop_close_airdrop() {
+    let mut test = TemplateTest::my_crate();
+    let airdrop_result = airdrop(&mut test);
+    let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
+
+    let owner_call = |method: &str| {
+        Transaction::builder_localnet()
+            .call_method(airdrop_result.airdrop_address, method, args![])
+            .call_method(airdrop_result.airdrop_address, "status", args![])
+            .build_and_seal(&airdrop_result.owner_secret)
+    };
+
+    // the airdrop must be open before it is closed
+    let reason = test.execute_expect_failure(
+        owner_call("close_airdrop"),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+    assert_reject_reason(reason, "Airdrop is not open");
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(
+                airdrop_result.airdrop_address,
+                "add_recipient",
+                args![account_component, 1],
+            )
+            .call_method(
+                airdrop_result.airdrop_address,
+                "open_airdrop",
+                args![],
+            )
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+    let result = test.execute_expect_success(
+        owner_call("close_airdrop"),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+    let status = result.finalize.execution_results[1]
+        .decode::<AirdropStatus>()
+        .unwrap();
+    assert_eq!(status, AirdropStatus::Closed);
+
+    // nothing can be claimed once the airdrop is closed, and it cannot be opened again
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(
+                airdrop_result.airdrop_address,
+                "claim_any",
+                args![account_component],
+            )
+            .build_and_seal(&account_secret_key),
+        vec![owner_proof],
+    );
+    assert_reject_reason(reason, "Airdrop is not open");
+
+    let reason = test.execute_expect_failure(
+        owner_call("open_airdrop"),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+    assert_reject_reason(reason, "Airdrop is closed");
+}
+
 #[test]
 fn test_airdrop_claim_any_success() {
     let mut test = TemplateTest::my_crate();
@@ -199,26 +311,31 @@ fn test_airdrop_claim_any_success() {
         .unwrap();
     assert_eq!(vault_balance, 100);
 
-    // claim
-    let result = test.execute_expect_success(
+    // add recipient and open airdrop
+    test.execute_expect_success(
         Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
-                "open_airdrop",
-                args![],
+                "add_recipient",
+                args![account_component, 1],
             )
             .call_method(
                 airdrop_result.airdrop_address,
-                "add_recipient",
-                args![account_component],
+                "open_airdrop",
+                args![],
             )
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+
+    // claim, the token is deposited into the account
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
                 "claim_any",
                 args![account_component],
             )
-            .put_last_instruction_output_on_workspace("airdrop")
-            .call_method(account_component, "deposit", args![Workspace("airdrop")])
             .build_and_seal(&account_secret_key),
         vec![owner_proof.clone()],
     );
@@ -273,8 +390,6 @@ fn test_airdrop_claim_any_airdrop_not_open() {
                 "claim_any",
                 args![account_component],
             )
-            .put_last_instruction_output_on_workspace("airdrop")
-            .call_method(account_component, "deposit", args![Workspace("airdrop")])
             .build_and_seal(&account_secret_key),
         vec![owner_proof.clone()],
     );
@@ -288,26 +403,30 @@ fn test_airdrop_claim_any_already_claimed() {
     let airdrop_result = airdrop(&mut test);
     let (account_component, owner_proof, account_secret_key) = test.create_funded_account();
 
-    // claim
-    let reject_reason = test.execute_expect_failure(
+    test.execute_expect_success(
         Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
-                "open_airdrop",
-                args![],
+                "add_recipient",
+                args![account_component, 1],
             )
             .call_method(
                 airdrop_result.airdrop_address,
-                "add_recipient",
-                args![account_component],
+                "open_airdrop",
+                args![],
             )
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+
+    // claim
+    let reject_reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
             .call_method(
                 airdrop_result.airdrop_address,
                 "claim_any",
                 args![account_component],
             )
-            .put_last_instruction_output_on_workspace("airdrop")
-            .call_method(account_component, "deposit", args![Workspace("airdrop")])
             .call_method(
                 airdrop_result.airdrop_address,
                 "claim_any",
@@ -318,4 +437,608 @@ fn test_airdrop_claim_any_already_claimed() {
     );
 
     assert_reject_reason(reject_reason, "is not in allow list or has already been claimed");
-}
\ No newline at end of file
+}
+
+// Builds a Merkle tree in the same way as the template, returning the root and the proof of each leaf
+fn merkle_tree(leaves: &[(u64, ComponentAddress, LeafPayload)]) -> (Hash32, Vec<Vec<Hash32>>) {
+    fn hash(prefix: u8, data: &[&[u8]]) -> Hash32 {
+        let mut hasher = Sha256::new();
+        hasher.update([prefix]);
+        for d in data {
+            hasher.update(d);
+        }
+        Hash32::from_array(hasher.finalize().into())
+    }
+    fn node_hash(a: &Hash32, b: &Hash32) -> Hash32 {
+        let (left, right) = if a <= b { (a, b) } else { (b, a) };
+        hash(1, &[left.as_slice(), right.as_slice()])
+    }
+
+    let mut level = leaves
+        .iter()
+        .map(|leaf| hash(0, &[&tari_bor::encode(leaf).unwrap()]))
+        .collect::<Vec<_>>();
+    let mut positions = (0..leaves.len()).collect::<Vec<_>>();
+    let mut proofs = vec![vec![]; leaves.len()];
+    while level.len() > 1 {
+        for (proof, position) in proofs.iter_mut().zip(&mut positions) {
+            // the last node of a level with an odd number of nodes moves up without a sibling
+            if let Some(sibling) = level.get(*position ^ 1) {
+                proof.push(*sibling);
+            }
+            *position /= 2;
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => node_hash(a, b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    (level[0], proofs)
+}
+
+fn merkle_airdrop(
+    test: &mut TemplateTest,
+    merkle_root: Hash32,
+) -> (ComponentAddress, NonFungibleAddress, RistrettoSecretKey) {
+    let (_account_component, owner_proof, account_secret_key) = test.create_funded_account();
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address("{{ project-name | upper_camel_case }}"),
+                "new_with_merkle_root",
+                args![merkle_root, 0u64, 100u64],
+            )
+            .build_and_seal(&account_secret_key),
+        vec![owner_proof.clone()],
+    );
+    let airdrop_address = result.finalize.execution_results[0]
+        .decode::<ComponentAddress>()
+        .unwrap();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "open_airdrop", args![])
+            .build_and_seal(&account_secret_key),
+        vec![owner_proof.clone()],
+    );
+    (airdrop_address, owner_proof, account_secret_key)
+}
+
+#[test]
+fn test_airdrop_merkle_claim_success() {
+    let mut test = TemplateTest::my_crate();
+    let recipients = (0..5)
+        .map(|_| test.create_funded_account())
+        .collect::<Vec<_>>();
+    let leaves = recipients
+        .iter()
+        .enumerate()
+        .map(|(i, (account, _, _))| {
+            let index = i as u64;
+            (index, *account, LeafPayload::NonFungible(NonFungibleId::from_u64(1000 + index)))
+        })
+        .collect::<Vec<_>>();
+    let (merkle_root, proofs) = merkle_tree(&leaves);
+    let (airdrop_address, owner_proof, owner_secret_key) = merkle_airdrop(&mut test, merkle_root);
+
+    // anyone can submit the claim, the token is deposited into the account of the leaf
+    let (payer_component, payer_proof, payer_secret_key) = test.create_funded_account();
+    for i in [4, 1] {
+        let (index, account, payload) = leaves[i].clone();
+        test.execute_expect_success(
+            Transaction::builder_localnet()
+                .call_method(
+                    airdrop_address,
+                    "claim",
+                    args![account, (index, payload), proofs[i].clone()],
+                )
+                .build_and_seal(&payer_secret_key),
+            vec![payer_proof.clone()],
+        );
+    }
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "num_claimed", args![])
+            .call_method(airdrop_address, "is_claimed", args![1u64])
+            .call_method(airdrop_address, "is_claimed", args![2u64])
+            .build_and_seal(&payer_secret_key),
+        vec![payer_proof.clone()],
+    );
+    assert_eq!(result.finalize.execution_results[0].decode::<u64>().unwrap(), 2);
+    assert!(result.finalize.execution_results[1].decode::<bool>().unwrap());
+    assert!(!result.finalize.execution_results[2].decode::<bool>().unwrap());
+
+    let (account, _, _) = &recipients[4];
+    let state_store = test.read_only_state_store();
+    let vault_id = state_store
+        .get_account(*account)
+        .unwrap()
+        .vaults()
+        .values()
+        .map(|vault| vault.vault_id())
+        .find(|vault_id| {
+            state_store
+                .get_vault(vault_id)
+                .unwrap()
+                .get_non_fungible_ids()
+                .contains(&NonFungibleId::from_u64(1004))
+        });
+    assert!(vault_id.is_some());
+
+    // leaves can only be claimed once
+    let (index, account, payload) = leaves[4].clone();
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(
+                airdrop_address,
+                "claim",
+                args![account, (index, payload), proofs[4].clone()],
+            )
+            .build_and_seal(&payer_secret_key),
+        vec![payer_proof.clone()],
+    );
+    assert_reject_reason(reason, "Leaf 4 has already been claimed");
+
+    // recipients are not added to an allow list
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "add_recipient", args![payer_component, 1])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof],
+    );
+    assert_reject_reason(reason, "Airdrop recipients are in a Merkle tree");
+}
+
+#[test]
+fn test_airdrop_merkle_fungible_claim_success() {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (owner_component, owner_proof, owner_secret_key) = test.create_funded_account();
+    let recipients = (0..3)
+        .map(|_| test.create_funded_account())
+        .collect::<Vec<_>>();
+    let mut leaves = recipients
+        .iter()
+        .enumerate()
+        .map(|(i, (account, _, _))| {
+            let index = i as u64;
+            (index, *account, LeafPayload::Amount(Amount::from(100 * (index + 1))))
+        })
+        .collect::<Vec<_>>();
+    // a leaf with a non-fungible id cannot be claimed from fungible tokens
+    leaves.push((3, owner_component, LeafPayload::NonFungible(NonFungibleId::from_u64(1))));
+    let (merkle_root, proofs) = merkle_tree(&leaves);
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address("TestTokens"),
+                "create",
+                args!["DROP", 1000],
+            )
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_function(
+                test.get_template_address("{{ project-name | upper_camel_case }}"),
+                "new_fungible_with_merkle_root",
+                args![Workspace("tokens"), merkle_root, 0u64, 100u64],
+            )
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+    let airdrop_address = result.finalize.execution_results[2]
+        .decode::<ComponentAddress>()
+        .unwrap();
+    let token_resource = result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "open_airdrop", args![])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+
+    // anyone can submit the claim, the amount of the leaf is deposited into its account
+    let (_, payer_proof, payer_secret_key) = test.create_funded_account();
+    let claim = |i: usize| {
+        let (index, account, payload) = leaves[i].clone();
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "claim", args![account, (index, payload), proofs[i].clone()])
+            .call_method(account, "balance", args![token_resource])
+            .build_and_seal(&payer_secret_key)
+    };
+    for (i, expected) in [(2, 300), (0, 100)] {
+        let result = test.execute_expect_success(claim(i), vec![payer_proof.clone()]);
+        let balance = result.finalize.execution_results[1]
+            .decode::<Amount>()
+            .unwrap();
+        assert_eq!(balance, expected);
+    }
+
+    // leaves can only be claimed once
+    let reason = test.execute_expect_failure(claim(2), vec![payer_proof.clone()]);
+    assert_reject_reason(reason, "Leaf 2 has already been claimed");
+
+    let reason = test.execute_expect_failure(claim(3), vec![payer_proof.clone()]);
+    assert_reject_reason(reason, "Airdrop leaves must have amounts");
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "num_claimed", args![])
+            .build_and_seal(&payer_secret_key),
+        vec![payer_proof],
+    );
+    assert_eq!(result.finalize.execution_results[0].decode::<u64>().unwrap(), 2);
+}
+
+#[test]
+fn test_airdrop_merkle_claim_invalid_proof() {
+    let mut test = TemplateTest::my_crate();
+    let recipients = (0..3)
+        .map(|_| test.create_funded_account())
+        .collect::<Vec<_>>();
+    let leaves = recipients
+        .iter()
+        .enumerate()
+        .map(|(i, (account, _, _))| (i as u64, *account, LeafPayload::NonFungible(NonFungibleId::from_u64(i as u64))))
+        .collect::<Vec<_>>();
+    let (merkle_root, proofs) = merkle_tree(&leaves);
+    let (airdrop_address, _, _) = merkle_airdrop(&mut test, merkle_root);
+    let (other_account, other_proof, other_secret_key) = test.create_funded_account();
+
+    let (index, account, payload) = leaves[0].clone();
+    let invalid_claims = [
+        // claiming the leaf of another account
+        args![other_account, (index, payload.clone()), proofs[0].clone()],
+        // claiming a different token
+        args![account, (index, LeafPayload::NonFungible(NonFungibleId::from_u64(99))), proofs[0].clone()],
+        // claiming with the proof of another leaf
+        args![account, (index, payload), proofs[1].clone()],
+    ];
+    for args in invalid_claims {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_method(airdrop_address, "claim", args)
+                .build_and_seal(&other_secret_key),
+            vec![other_proof.clone()],
+        );
+        assert_reject_reason(reason, "Invalid Merkle proof");
+    }
+}
+
+#[test]
+fn test_airdrop_fungible_claim_and_reclaim() {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (owner_component, owner_proof, owner_secret_key) = test.create_funded_account();
+    let (alice_component, alice_proof, alice_secret_key) = test.create_funded_account();
+    let (bob_component, bob_proof, bob_secret_key) = test.create_funded_account();
+    let (carol_component, _, _) = test.create_funded_account();
+
+    // fund the airdrop with 1000 tokens, which can be claimed until epoch 10 and then reclaimed
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address("TestTokens"),
+                "create",
+                args!["DROP", 1000],
+            )
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_function(
+                test.get_template_address("{{ project-name | upper_camel_case }}"),
+                "new_fungible",
+                args![Workspace("tokens"), 0u64, 10u64],
+            )
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+    let airdrop_address = result.finalize.execution_results[2]
+        .decode::<ComponentAddress>()
+        .unwrap();
+    let token_resource = result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "add_recipient", args![alice_component, 300])
+            .call_method(airdrop_address, "add_recipient", args![bob_component, 500])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+
+    // recipients cannot be allocated more tokens than the airdrop holds
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "add_recipient", args![carol_component, 300])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+    assert_reject_reason(reason, "Airdrop does not have enough tokens for the recipient");
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "open_airdrop", args![])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+
+    let claim = |account: ComponentAddress, secret_key: &RistrettoSecretKey| {
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "claim_any", args![account])
+            .call_method(account, "balance", args![token_resource])
+            .build_and_seal(secret_key)
+    };
+    let result = test.execute_expect_success(claim(alice_component, &alice_secret_key), vec![alice_proof]);
+    let balance = result.finalize.execution_results[1]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(balance, 300);
+
+    let reclaim = |secret_key: &RistrettoSecretKey| {
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "reclaim_unclaimed", args![])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(owner_component, "deposit", args![Workspace("tokens")])
+            .call_method(owner_component, "balance", args![token_resource])
+            .build_and_seal(secret_key)
+    };
+    let reason = test.execute_expect_failure(reclaim(&owner_secret_key), vec![owner_proof.clone()]);
+    assert_reject_reason(reason, "Unclaimed tokens cannot be reclaimed before epoch 10");
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(10));
+
+    // only the owner can reclaim the tokens
+    let reason = test.execute_expect_failure(reclaim(&bob_secret_key), vec![bob_proof.clone()]);
+    assert_reject_reason(reason, "Access Denied");
+
+    let result = test.execute_expect_success(reclaim(&owner_secret_key), vec![owner_proof]);
+    let balance = result.finalize.execution_results[3]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(balance, 700);
+
+    // recipients that did not claim in time can no longer claim
+    let reason = test.execute_expect_failure(claim(bob_component, &bob_secret_key), vec![bob_proof]);
+    assert_reject_reason(reason, "Airdrop is not open");
+}
+
+#[test]
+fn test_airdrop_vesting_claim_and_release() {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (owner_component, owner_proof, owner_secret_key) = test.create_funded_account();
+    let (alice_component, alice_proof, alice_secret_key) = test.create_funded_account();
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(0));
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address("TestTokens"),
+                "create",
+                args!["DROP", 1000],
+            )
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(owner_component, "deposit", args![Workspace("tokens")])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+    let token_resource = result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap();
+
+    // claims vest over 100 epochs with a cliff of 10 epochs
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(owner_component, "withdraw", args![token_resource, 1000])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_function(
+                test.get_template_address("{{ project-name | upper_camel_case }}"),
+                "new_vesting",
+                args![Workspace("tokens"), 0u64, 500u64, 10u64, 100u64],
+            )
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+    let airdrop_address = result.finalize.execution_results[2]
+        .decode::<ComponentAddress>()
+        .unwrap();
+    let position_resource = result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .filter_map(|(id, _)| id.as_resource_address())
+        .find(|resource| *resource != token_resource)
+        .unwrap();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "add_recipient", args![alice_component, 1000])
+            .call_method(airdrop_address, "open_airdrop", args![])
+            .build_and_seal(&owner_secret_key),
+        vec![owner_proof.clone()],
+    );
+
+    // the claim deposits a vesting position instead of the tokens
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(airdrop_address, "claim_any", args![alice_component])
+            .call_method(alice_component, "balance", args![token_resource])
+            .call_method(alice_component, "balance", args![position_resource])
+            .build_and_seal(&alice_secret_key),
+        vec![alice_proof.clone()],
+    );
+    assert_eq!(result.finalize.execution_results[1].decode::<Amount>().unwrap(), 0);
+    assert_eq!(result.finalize.execution_results[2].decode::<Amount>().unwrap(), 1);
+
+    let release = || {
+        Transaction::builder_localnet()
+            .create_proof(alice_component, position_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(airdrop_address, "release", args![Workspace("proof")])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(alice_component, "deposit", args![Workspace("tokens")])
+            .drop_all_proofs_in_workspace()
+            .call_method(alice_component, "balance", args![token_resource])
+            .build_and_seal(&alice_secret_key)
+    };
+    let alice_balance = |result: &ExecuteResult| {
+        result
+            .finalize
+            .execution_results
+            .last()
+            .unwrap()
+            .decode::<Amount>()
+            .unwrap()
+    };
+
+    // nothing can be released before the cliff
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(5));
+    let reason = test.execute_expect_failure(release(), vec![alice_proof.clone()]);
+    assert_reject_reason(reason, "is locked until epoch 10");
+
+    // after the cliff the tokens unlock linearly from the claim
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(25));
+    let result = test.execute_expect_success(release(), vec![alice_proof.clone()]);
+    assert_eq!(alice_balance(&result), 250);
+    let topic = format!("{}.release", "{{ project-name | upper_camel_case }}");
+    let event = result
+        .finalize
+        .events
+        .iter()
+        .find(|e| e.topic() == topic)
+        .unwrap();
+    assert_eq!(event.get_payload("amount").unwrap(), "250");
+    assert_eq!(event.get_payload("released").unwrap(), "250");
+
+    let reason = test.execute_expect_failure(release(), vec![alice_proof.clone()]);
+    assert_reject_reason(reason, "have unlocked since the last release");
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(60));
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(
+                airdrop_address,
+                "releasable_amount",
+                args![NonFungibleId::from_u64(1)],
+            )
+            .build_and_seal(&alice_secret_key),
+        vec![alice_proof.clone()],
+    );
+    assert_eq!(result.finalize.execution_results[0].decode::<Amount>().unwrap(), 350);
+
+    // everything is released once the vesting has ended
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(200));
+    let result = test.execute_expect_success(release(), vec![alice_proof]);
+    assert_eq!(alice_balance(&result), 1000);
+}
+
+#[test]
+fn test_airdrop_claim_window_and_close_and_sweep() {
+    let mut test = TemplateTest::my_crate();
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(0));
+    let airdrop_result = airdrop_with_claim_window(&mut test, 5, 20);
+    let recipients = (0..3)
+        .map(|_| test.create_funded_account())
+        .collect::<Vec<_>>();
+
+    let mut builder = Transaction::builder_localnet();
+    for (account, _, _) in &recipients {
+        builder = builder.call_method(
+            airdrop_result.airdrop_address,
+            "add_recipient",
+            args![*account, 1],
+        );
+    }
+    test.execute_expect_success(
+        builder
+            .call_method(airdrop_result.airdrop_address, "open_airdrop", args![])
+            .build_and_seal(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+
+    let claim = |(account, _, secret_key): &(ComponentAddress, NonFungibleAddress, RistrettoSecretKey)| {
+        Transaction::builder_localnet()
+            .call_method(airdrop_result.airdrop_address, "claim_any", args![*account])
+            .build_and_seal(secret_key)
+    };
+
+    // tokens can only be claimed within the window
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(2));
+    let reason = test.execute_expect_failure(claim(&recipients[0]), vec![recipients[0].1.clone()]);
+    assert_reject_reason(reason, "Airdrop claims start at epoch 5");
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(5));
+    test.execute_expect_success(claim(&recipients[0]), vec![recipients[0].1.clone()]);
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(20));
+    let reason = test.execute_expect_failure(claim(&recipients[1]), vec![recipients[1].1.clone()]);
+    assert_reject_reason(reason, "Airdrop claims ended at epoch 20");
+
+    // only the owner can close the airdrop and sweep the unclaimed tokens
+    let (treasury, _, _) = test.create_empty_account();
+    let close_and_sweep = |secret_key: &RistrettoSecretKey| {
+        Transaction::builder_localnet()
+            .call_method(
+                airdrop_result.airdrop_address,
+                "close_and_sweep",
+                args![treasury],
+            )
+            .call_method(airdrop_result.airdrop_address, "vault_balance", args![])
+            .build_and_seal(secret_key)
+    };
+    let reason = test.execute_expect_failure(close_and_sweep(&recipients[1].2), vec![recipients[1].1.clone()]);
+    assert_reject_reason(reason, "Access Denied");
+
+    let result = test.execute_expect_success(
+        close_and_sweep(&airdrop_result.owner_secret),
+        vec![airdrop_result.owner_proof.clone()],
+    );
+    assert_eq!(result.finalize.execution_results[1].decode::<Amount>().unwrap(), 0);
+    let topic = format!("{}.close_and_sweep", "{{ project-name | upper_camel_case }}");
+    let event = result
+        .finalize
+        .events
+        .iter()
+        .find(|e| e.topic() == topic)
+        .unwrap();
+    assert_eq!(event.get_payload("claimed").unwrap(), "1");
+    // 99 of the 100 tokens were not claimed
+    assert_eq!(event.get_payload("unclaimed").unwrap(), "99");
+
+    let state_store = test.read_only_state_store();
+    let swept = state_store
+        .get_account(treasury)
+        .unwrap()
+        .vaults()
+        .values()
+        .map(|vault| {
+            state_store
+                .get_vault(&vault.vault_id())
+                .unwrap()
+                .get_non_fungible_ids()
+                .len()
+        })
+        .sum::<usize>();
+    assert_eq!(swept, 99);
+}
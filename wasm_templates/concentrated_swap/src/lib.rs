@@ -0,0 +1,506 @@
+mod liquidity_math;
+
+use liquidity_math::{
+    a_amount_delta, amounts_for_liquidity, b_amount_delta, liquidity_for_amounts, next_sqrt_price_from_a_input,
+    next_sqrt_price_from_b_input, price_from_sqrt_price, sqrt_price_from_price,
+};
+use std::collections::BTreeMap;
+use tari_template_lib::prelude::*;
+
+// Fees are represented as a per-mil quantity (e.g. "1" represents "0.1%")
+const PER_MIL: u64 = 1000;
+
+// Immutable data of a position NFT, the price range is fixed when the position is minted
+// prices are the amount of B tokens for one A token, as fixed point numbers with 12 decimals
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct PositionData {
+    #[n(0)]
+    pub lower_price: Amount,
+    #[n(1)]
+    pub upper_price: Amount,
+}
+
+// Mutable data of a position NFT, updated every time the liquidity or the fees of the position change
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct PositionMutableData {
+    #[n(0)]
+    pub liquidity: Amount,
+    // fees earned by the position that have not been collected yet
+    #[n(1)]
+    pub a_fees: Amount,
+    #[n(2)]
+    pub b_fees: Amount,
+}
+
+// State of a position kept by the pool, so swaps do not need to read every position NFT
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct Position {
+    #[n(0)]
+    pub lower_sqrt_price: Amount,
+    #[n(1)]
+    pub upper_sqrt_price: Amount,
+    #[n(2)]
+    pub liquidity: Amount,
+    #[n(3)]
+    pub a_fees: Amount,
+    #[n(4)]
+    pub b_fees: Amount,
+}
+
+impl Position {
+    fn mutable_data(&self) -> PositionMutableData {
+        PositionMutableData {
+            liquidity: self.liquidity,
+            a_fees: self.a_fees,
+            b_fees: self.b_fees,
+        }
+    }
+}
+
+#[template]
+mod {{ project-name | snake_case }} {
+    use super::*;
+
+    // AMM with concentrated liquidity
+    // each position provides liquidity only inside its price range, and earns the fees of the swaps in that range
+    pub struct {{ project-name | upper_camel_case }}Pool {
+        a_vault: Vault,
+        b_vault: Vault,
+        // square root of the current price of A in B tokens, see "liquidity_math"
+        sqrt_price: Amount,
+        fee: u16,
+        position_resource: ResourceManager,
+        position_count: u64,
+        positions: BTreeMap<NonFungibleId, Position>,
+    }
+
+    impl {{ project-name | upper_camel_case }}Pool {
+        // Initialises a new pool component for for the pool A - B
+        // the fees is represented as a per-mil quantity (e.g. "1" represents "0.1%")
+        // the initial price is the amount of B tokens for one A token, as a fixed point number with 12 decimals
+        pub fn new(
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            initial_price: Amount,
+        ) -> Component<Self> {
+            // check that the the resource pair is correct
+            assert_ne!(a_addr, b_addr, "The resources of the pair must be different");
+            Self::check_resource_is_fungible(a_addr);
+            Self::check_resource_is_fungible(b_addr);
+
+            // the fee is per-mil, so it must be less than 1000 (100%)
+            assert!(u64::from(fee) < PER_MIL, "Invalid fee {}", fee);
+
+            let sqrt_price = sqrt_price_from_price(initial_price);
+            assert!(!sqrt_price.is_zero(), "Invalid initial price {}", initial_price);
+
+            // allocate the component address up front, so only this component is allowed to manage the positions
+            let component_alloc = CallerContext::allocate_component_address(None);
+            let require_pool = rule!(component(component_alloc.get_address()));
+
+            // create the position resource
+            // LOCKED makes the rules immutable, and with no owner nobody else can bypass them
+            let position_resource = ResourceBuilder::non_fungible()
+                .with_token_symbol("POS")
+                .with_owner_rule(OwnerRule::None)
+                .mintable(require_pool.clone(), LOCKED)
+                .burnable(require_pool.clone(), LOCKED)
+                .update_non_fungible_data(require_pool, LOCKED)
+                .build();
+
+            Component::new(Self {
+                a_vault: Vault::new_empty(a_addr),
+                b_vault: Vault::new_empty(b_addr),
+                sqrt_price,
+                fee,
+                position_resource: ResourceManager::get(position_resource),
+                position_count: 0,
+                positions: BTreeMap::new(),
+            })
+            .with_address_allocation(component_alloc)
+            .with_access_rules(AccessRules::allow_all())
+            .create()
+        }
+
+        // swap A tokens for B tokens or vice versa
+        // WARNING: no slippage protection is applied, use "swap_exact_in" instead
+        pub fn swap(&mut self, input_bucket: Bucket, output_resource: ResourceAddress) -> Bucket {
+            self.perform_swap(input_bucket, output_resource)
+        }
+
+        // swap all the tokens in the input bucket
+        // the transaction is rejected if the output is lower than "min_output" or the epoch "valid_until_epoch" has passed
+        pub fn swap_exact_in(
+            &mut self,
+            input_bucket: Bucket,
+            output_resource: ResourceAddress,
+            min_output: Amount,
+            valid_until_epoch: u64,
+        ) -> Bucket {
+            let current_epoch = Consensus::current_epoch();
+            assert!(
+                current_epoch <= valid_until_epoch,
+                "Swap deadline has passed: current epoch {} is after {}",
+                current_epoch,
+                valid_until_epoch
+            );
+
+            let output_bucket = self.perform_swap(input_bucket, output_resource);
+            assert!(
+                output_bucket.amount() >= min_output,
+                "Insufficient output amount: {} is lower than the minimum {}",
+                output_bucket.amount(),
+                min_output
+            );
+
+            output_bucket
+        }
+
+        // provides liquidity in the price range [lower_price, upper_price], returning the new position NFT
+        // alongside the tokens not needed by the position
+        // below the current price the position only needs B tokens, and above it only A tokens
+        pub fn mint_position(
+            &mut self,
+            a_bucket: Bucket,
+            b_bucket: Bucket,
+            lower_price: Amount,
+            upper_price: Amount,
+        ) -> (Bucket, Bucket, Bucket) {
+            let lower_sqrt_price = sqrt_price_from_price(lower_price);
+            let upper_sqrt_price = sqrt_price_from_price(upper_price);
+            assert!(
+                !lower_sqrt_price.is_zero() && lower_sqrt_price < upper_sqrt_price,
+                "Invalid price range {} - {}",
+                lower_price,
+                upper_price
+            );
+
+            let mut position = Position {
+                lower_sqrt_price,
+                upper_sqrt_price,
+                liquidity: Amount::zero(),
+                a_fees: Amount::zero(),
+                b_fees: Amount::zero(),
+            };
+            let (a_refund, b_refund) = self.deposit_liquidity(&mut position, a_bucket, b_bucket);
+
+            let id = NonFungibleId::from_u64(self.position_count);
+            self.position_count += 1;
+            let data = PositionData {
+                lower_price,
+                upper_price,
+            };
+            let position_bucket = self
+                .position_resource
+                .mint_non_fungible(id.clone(), &data, &position.mutable_data());
+            self.positions.insert(id, position);
+
+            (position_bucket, a_refund, b_refund)
+        }
+
+        // adds liquidity to an existing position, in the same price range
+        // returns the tokens not needed by the position
+        pub fn increase_liquidity(
+            &mut self,
+            position_proof: Proof,
+            a_bucket: Bucket,
+            b_bucket: Bucket,
+        ) -> (Bucket, Bucket) {
+            let id = self.get_position_id(&position_proof);
+            let mut position = self.positions.get(&id).unwrap().clone();
+
+            let refunds = self.deposit_liquidity(&mut position, a_bucket, b_bucket);
+            self.update_position(id, position);
+
+            refunds
+        }
+
+        // withdraws the fees earned by a position, the liquidity stays in the pool
+        pub fn collect_fees(&mut self, position_proof: Proof) -> (Bucket, Bucket) {
+            let id = self.get_position_id(&position_proof);
+            let mut position = self.positions.get(&id).unwrap().clone();
+
+            let a_bucket = self.a_vault.withdraw(position.a_fees);
+            let b_bucket = self.b_vault.withdraw(position.b_fees);
+            position.a_fees = Amount::zero();
+            position.b_fees = Amount::zero();
+            self.update_position(id, position);
+
+            (a_bucket, b_bucket)
+        }
+
+        // burns a position NFT, returning all of its liquidity alongside the uncollected fees
+        pub fn burn_position(&mut self, position_bucket: Bucket) -> (Bucket, Bucket) {
+            assert_eq!(
+                position_bucket.resource_address(),
+                self.position_resource.resource_address(),
+                "Invalid position resource"
+            );
+            let ids = position_bucket.get_non_fungible_ids();
+            assert_eq!(ids.len(), 1, "Positions must be burned one at a time");
+            let position = self.positions.remove(&ids[0]).unwrap();
+
+            // the amounts are rounded down in favour of the pool
+            let (a_amount, b_amount) = amounts_for_liquidity(
+                self.sqrt_price,
+                position.lower_sqrt_price,
+                position.upper_sqrt_price,
+                position.liquidity,
+                false,
+            );
+
+            position_bucket.burn();
+
+            let a_bucket = self.a_vault.withdraw(a_amount + position.a_fees);
+            let b_bucket = self.b_vault.withdraw(b_amount + position.b_fees);
+            (a_bucket, b_bucket)
+        }
+
+        pub fn get_a_resource(&self) -> ResourceAddress {
+            self.a_vault.resource_address()
+        }
+
+        pub fn get_b_resource(&self) -> ResourceAddress {
+            self.b_vault.resource_address()
+        }
+
+        pub fn get_position_resource(&self) -> ResourceAddress {
+            self.position_resource.resource_address()
+        }
+
+        // returns the current amount of B tokens for one A token, as a fixed point number with 12 decimals
+        pub fn get_price(&self) -> Amount {
+            price_from_sqrt_price(self.sqrt_price)
+        }
+
+        pub fn get_pool_balances(&self) -> BTreeMap<ResourceAddress, Amount> {
+            let mut balances = BTreeMap::new();
+            balances.insert(self.get_a_resource(), self.a_vault.balance());
+            balances.insert(self.get_b_resource(), self.b_vault.balance());
+            balances
+        }
+
+        pub fn get_position(&self, id: NonFungibleId) -> PositionMutableData {
+            self.positions
+                .get(&id)
+                .unwrap_or_else(|| panic!("Position {} does not exist", id))
+                .mutable_data()
+        }
+
+        // returns the liquidity of all the positions in range of the current price
+        pub fn get_active_liquidity(&self) -> Amount {
+            self.positions
+                .values()
+                .filter(|p| p.lower_sqrt_price <= self.sqrt_price && self.sqrt_price < p.upper_sqrt_price)
+                .fold(Amount::zero(), |total, p| total + p.liquidity)
+        }
+
+        pub fn fee(&self) -> u16 {
+            self.fee
+        }
+
+        // swaps through the positions in the direction of the price movement, one price range at a time
+        fn perform_swap(&mut self, input_bucket: Bucket, output_resource: ResourceAddress) -> Bucket {
+            let input_resource = input_bucket.resource_address();
+            let a_resource = self.get_a_resource();
+            let b_resource = self.get_b_resource();
+            assert!(
+                (input_resource == a_resource && output_resource == b_resource)
+                    || (input_resource == b_resource && output_resource == a_resource),
+                "Invalid swap resources {} - {}",
+                input_resource,
+                output_resource
+            );
+            let is_a_input = input_resource == a_resource;
+
+            // apply the fee to the input amount, the fee is shared by the positions that provide the liquidity
+            let input_amount = input_bucket.amount();
+            let effective_input_amount = input_amount - (input_amount * Amount::from(self.fee)) / Amount::from(PER_MIL);
+            let fee_amount = input_amount - effective_input_amount;
+
+            let mut remaining = effective_input_amount;
+            let mut output_amount = Amount::zero();
+            while !remaining.is_zero() {
+                // selling A moves the price down and selling B moves the price up, until the next position bound
+                let target_sqrt_price = self
+                    .get_next_sqrt_price_bound(is_a_input)
+                    .unwrap_or_else(|| panic!("Insufficient liquidity in the pool for resource '{}'", output_resource));
+                let (lower, upper) = if is_a_input {
+                    (target_sqrt_price, self.sqrt_price)
+                } else {
+                    (self.sqrt_price, target_sqrt_price)
+                };
+
+                let liquidity = self.get_liquidity_between(lower, upper);
+                if liquidity.is_zero() {
+                    // there are no positions in this range, so the price jumps to the next one
+                    self.sqrt_price = target_sqrt_price;
+                    continue;
+                }
+
+                // the input needed to reach the bound is rounded up, and the output rounded down, in favour of the pool
+                let max_input = if is_a_input {
+                    a_amount_delta(lower, upper, liquidity, true)
+                } else {
+                    b_amount_delta(lower, upper, liquidity, true)
+                };
+                let (step_input, new_sqrt_price) = if remaining >= max_input {
+                    (max_input, target_sqrt_price)
+                } else if is_a_input {
+                    (
+                        remaining,
+                        next_sqrt_price_from_a_input(self.sqrt_price, liquidity, remaining),
+                    )
+                } else {
+                    (
+                        remaining,
+                        next_sqrt_price_from_b_input(self.sqrt_price, liquidity, remaining),
+                    )
+                };
+
+                output_amount += if is_a_input {
+                    b_amount_delta(new_sqrt_price, self.sqrt_price, liquidity, false)
+                } else {
+                    a_amount_delta(self.sqrt_price, new_sqrt_price, liquidity, false)
+                };
+
+                // the positions earn fees proportionally to the input they processed
+                let step_fee =
+                    fee_amount.checked_mul(step_input).expect("Fee calculation overflow") / effective_input_amount;
+                self.distribute_fees(lower, upper, liquidity, step_fee, is_a_input);
+
+                remaining -= step_input;
+                self.sqrt_price = new_sqrt_price;
+            }
+
+            if is_a_input {
+                self.a_vault.deposit(input_bucket);
+                self.b_vault.withdraw(output_amount)
+            } else {
+                self.b_vault.deposit(input_bucket);
+                self.a_vault.withdraw(output_amount)
+            }
+        }
+
+        // closest position bound below (when the price goes down) or above (when the price goes up) the current price
+        fn get_next_sqrt_price_bound(&self, is_price_down: bool) -> Option<Amount> {
+            let bounds = self
+                .positions
+                .values()
+                .filter(|p| !p.liquidity.is_zero())
+                .flat_map(|p| [p.lower_sqrt_price, p.upper_sqrt_price]);
+
+            if is_price_down {
+                bounds.filter(|b| *b < self.sqrt_price).max()
+            } else {
+                bounds.filter(|b| *b > self.sqrt_price).min()
+            }
+        }
+
+        // total liquidity of the positions that cover the whole price range
+        fn get_liquidity_between(&self, lower_sqrt_price: Amount, upper_sqrt_price: Amount) -> Amount {
+            self.positions
+                .values()
+                .filter(|p| p.lower_sqrt_price <= lower_sqrt_price && upper_sqrt_price <= p.upper_sqrt_price)
+                .fold(Amount::zero(), |total, p| total + p.liquidity)
+        }
+
+        // shares the fees of a swap between the positions that cover its price range
+        // rounding leftovers stay in the pool
+        fn distribute_fees(
+            &mut self,
+            lower_sqrt_price: Amount,
+            upper_sqrt_price: Amount,
+            liquidity: Amount,
+            fee_amount: Amount,
+            is_a_fee: bool,
+        ) {
+            let ids: Vec<NonFungibleId> = self
+                .positions
+                .iter()
+                .filter(|(_, p)| p.lower_sqrt_price <= lower_sqrt_price && upper_sqrt_price <= p.upper_sqrt_price)
+                .map(|(id, _)| id.clone())
+                .collect();
+
+            for id in ids {
+                let mut position = self.positions.get(&id).unwrap().clone();
+                let position_fee = fee_amount
+                    .checked_mul(position.liquidity)
+                    .expect("Fee calculation overflow")
+                    / liquidity;
+                if position_fee.is_zero() {
+                    continue;
+                }
+                if is_a_fee {
+                    position.a_fees += position_fee;
+                } else {
+                    position.b_fees += position_fee;
+                }
+                self.update_position(id, position);
+            }
+        }
+
+        // adds as much liquidity to the position as the buckets allow at the current price, returning the excess tokens
+        fn deposit_liquidity(
+            &mut self,
+            position: &mut Position,
+            mut a_bucket: Bucket,
+            mut b_bucket: Bucket,
+        ) -> (Bucket, Bucket) {
+            assert_eq!(a_bucket.resource_address(), self.get_a_resource(), "Invalid A resource");
+            assert_eq!(b_bucket.resource_address(), self.get_b_resource(), "Invalid B resource");
+
+            let liquidity = liquidity_for_amounts(
+                self.sqrt_price,
+                position.lower_sqrt_price,
+                position.upper_sqrt_price,
+                a_bucket.amount(),
+                b_bucket.amount(),
+            );
+            assert!(!liquidity.is_zero(), "Insufficient liquidity provided");
+
+            // the amounts are rounded up in favour of the pool
+            let (a_amount, b_amount) = amounts_for_liquidity(
+                self.sqrt_price,
+                position.lower_sqrt_price,
+                position.upper_sqrt_price,
+                liquidity,
+                true,
+            );
+            // out of range positions only hold one of the resources
+            if !a_amount.is_zero() {
+                self.a_vault.deposit(a_bucket.take(a_amount));
+            }
+            if !b_amount.is_zero() {
+                self.b_vault.deposit(b_bucket.take(b_amount));
+            }
+            position.liquidity += liquidity;
+
+            (a_bucket, b_bucket)
+        }
+
+        // stores the new state of a position, keeping the NFT data in sync
+        fn update_position(&mut self, id: NonFungibleId, position: Position) {
+            self.position_resource
+                .update_non_fungible_data(id.clone(), &position.mutable_data());
+            self.positions.insert(id, position);
+        }
+
+        fn get_position_id(&self, position_proof: &Proof) -> NonFungibleId {
+            position_proof.assert_resource(self.position_resource.resource_address());
+            let ids = position_proof.get_non_fungibles();
+            assert_eq!(ids.len(), 1, "The proof must contain exactly one position");
+            ids.into_iter().next().unwrap()
+        }
+
+        fn check_resource_is_fungible(resource: ResourceAddress) {
+            assert_eq!(
+                ResourceManager::get(resource).resource_type(),
+                ResourceType::Fungible,
+                "Resource {} is not fungible",
+                resource
+            );
+        }
+    }
+}
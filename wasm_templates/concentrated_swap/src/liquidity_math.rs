@@ -0,0 +1,157 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+// Math for concentrated liquidity, where a position with liquidity "L" in the price range [Pl, Pu] behaves like a
+// constant product pool "x * y = L^2" while the pool price P is inside the range. The amounts of a position are:
+//   "x = L * (1 / sqrt(P) - 1 / sqrt(Pu))" and "y = L * (sqrt(P) - sqrt(Pl))"
+// Prices are the amount of B tokens for one A token, and the calculations work with their square roots
+
+use tari_template_lib::types::Amount;
+
+// Prices are fixed point numbers with 12 decimals (e.g. "1_500_000_000_000" represents a price of 1.5)
+pub const PRICE_SCALE: u128 = 1_000_000_000_000;
+
+// Square roots of prices are fixed point numbers with 9 decimals, so "sqrt(price * PRICE_SCALE) * SQRT_PRICE_SCALE"
+// is the same as "sqrt(price * PRICE_SCALE * 1_000_000)"
+pub const SQRT_PRICE_SCALE: u128 = 1_000_000_000;
+const SQRT_CONVERSION: u128 = SQRT_PRICE_SCALE * SQRT_PRICE_SCALE / PRICE_SCALE;
+
+const OVERFLOW: &str = "Concentrated liquidity calculation overflow";
+
+pub fn sqrt_price_from_price(price: Amount) -> Amount {
+    sqrt(mul(price, Amount::new(SQRT_CONVERSION)))
+}
+
+pub fn price_from_sqrt_price(sqrt_price: Amount) -> Amount {
+    mul(sqrt_price, sqrt_price) / Amount::new(SQRT_CONVERSION)
+}
+
+// amount of A tokens of the liquidity between two prices
+pub fn a_amount_delta(
+    lower_sqrt_price: Amount,
+    upper_sqrt_price: Amount,
+    liquidity: Amount,
+    round_up: bool,
+) -> Amount {
+    let numerator = mul(
+        mul(liquidity, Amount::new(SQRT_PRICE_SCALE)),
+        upper_sqrt_price - lower_sqrt_price,
+    );
+    let denominator = mul(upper_sqrt_price, lower_sqrt_price);
+    divide(numerator, denominator, round_up)
+}
+
+// amount of B tokens of the liquidity between two prices
+pub fn b_amount_delta(
+    lower_sqrt_price: Amount,
+    upper_sqrt_price: Amount,
+    liquidity: Amount,
+    round_up: bool,
+) -> Amount {
+    let numerator = mul(liquidity, upper_sqrt_price - lower_sqrt_price);
+    divide(numerator, Amount::new(SQRT_PRICE_SCALE), round_up)
+}
+
+// amounts of each resource of a position at the current price
+pub fn amounts_for_liquidity(
+    sqrt_price: Amount,
+    lower_sqrt_price: Amount,
+    upper_sqrt_price: Amount,
+    liquidity: Amount,
+    round_up: bool,
+) -> (Amount, Amount) {
+    if sqrt_price <= lower_sqrt_price {
+        // below the range the position only holds A tokens
+        (
+            a_amount_delta(lower_sqrt_price, upper_sqrt_price, liquidity, round_up),
+            Amount::zero(),
+        )
+    } else if sqrt_price >= upper_sqrt_price {
+        // above the range the position only holds B tokens
+        (
+            Amount::zero(),
+            b_amount_delta(lower_sqrt_price, upper_sqrt_price, liquidity, round_up),
+        )
+    } else {
+        (
+            a_amount_delta(sqrt_price, upper_sqrt_price, liquidity, round_up),
+            b_amount_delta(lower_sqrt_price, sqrt_price, liquidity, round_up),
+        )
+    }
+}
+
+// maximum liquidity that can be provided with the amounts at the current price, rounded down
+pub fn liquidity_for_amounts(
+    sqrt_price: Amount,
+    lower_sqrt_price: Amount,
+    upper_sqrt_price: Amount,
+    a_amount: Amount,
+    b_amount: Amount,
+) -> Amount {
+    let liquidity_for_a = |lower: Amount, upper: Amount| {
+        mul(mul(a_amount, lower), upper) / mul(Amount::new(SQRT_PRICE_SCALE), upper - lower)
+    };
+    let liquidity_for_b =
+        |lower: Amount, upper: Amount| mul(b_amount, Amount::new(SQRT_PRICE_SCALE)) / (upper - lower);
+
+    if sqrt_price <= lower_sqrt_price {
+        liquidity_for_a(lower_sqrt_price, upper_sqrt_price)
+    } else if sqrt_price >= upper_sqrt_price {
+        liquidity_for_b(lower_sqrt_price, upper_sqrt_price)
+    } else {
+        liquidity_for_a(sqrt_price, upper_sqrt_price)
+            .min(liquidity_for_b(lower_sqrt_price, sqrt_price))
+    }
+}
+
+// new price after adding A tokens to the liquidity, rounded up so the price moves less in favour of the pool
+pub fn next_sqrt_price_from_a_input(
+    sqrt_price: Amount,
+    liquidity: Amount,
+    a_amount: Amount,
+) -> Amount {
+    let scaled_liquidity = mul(liquidity, Amount::new(SQRT_PRICE_SCALE));
+    mul(scaled_liquidity, sqrt_price).div_ceil(add(scaled_liquidity, mul(a_amount, sqrt_price)))
+}
+
+// new price after adding B tokens to the liquidity, rounded down so the price moves less in favour of the pool
+pub fn next_sqrt_price_from_b_input(
+    sqrt_price: Amount,
+    liquidity: Amount,
+    b_amount: Amount,
+) -> Amount {
+    add(sqrt_price, mul(b_amount, Amount::new(SQRT_PRICE_SCALE)) / liquidity)
+}
+
+// products of liquidity and prices can exceed u128 with large amounts, so they are checked to fail instead of wrapping
+fn mul(a: Amount, b: Amount) -> Amount {
+    a.checked_mul(b).expect(OVERFLOW)
+}
+
+fn add(a: Amount, b: Amount) -> Amount {
+    a.checked_add(b).expect(OVERFLOW)
+}
+
+fn divide(numerator: Amount, denominator: Amount, round_up: bool) -> Amount {
+    if round_up {
+        numerator.div_ceil(denominator)
+    } else {
+        numerator / denominator
+    }
+}
+
+// integer square root, rounded down, using Newton's method
+fn sqrt(value: Amount) -> Amount {
+    let n = value.to_u128();
+    if n < 2 {
+        return value;
+    }
+
+    let mut x = n;
+    let mut y = n / 2 + (n & 1);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    Amount::new(x)
+}
@@ -0,0 +1,30 @@
+use tari_template_lib::prelude::*;
+
+// Helper template used by the pool tests to create the fungible resources to be swapped,
+// and to try to mint, burn and update position NFTs from outside the pool component
+#[template]
+mod test_tokens {
+    use super::*;
+
+    pub struct TestTokens {}
+
+    impl TestTokens {
+        pub fn create(symbol: String, initial_supply: Amount) -> Bucket {
+            ResourceBuilder::public_fungible()
+                .with_token_symbol(symbol)
+                .initial_supply(initial_supply)
+        }
+
+        pub fn mint_non_fungible(resource: ResourceAddress, id: NonFungibleId) -> Bucket {
+            ResourceManager::get(resource).mint_non_fungible(id, &(), &())
+        }
+
+        pub fn burn(bucket: Bucket) {
+            bucket.burn();
+        }
+
+        pub fn update_non_fungible_data(resource: ResourceAddress, id: NonFungibleId) {
+            ResourceManager::get(resource).update_non_fungible_data(id, &());
+        }
+    }
+}
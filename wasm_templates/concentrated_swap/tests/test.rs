@@ -0,0 +1,553 @@
+use std::collections::BTreeMap;
+use tari_template_lib::prelude::{
+    Amount, ComponentAddress, NonFungibleId, ResourceAddress, ResourceAuthAction,
+};
+use tari_template_lib::types::NonFungibleAddress;
+use tari_template_test_tooling::crypto::RistrettoSecretKey;
+use tari_template_test_tooling::engine_types::substate::SubstateId;
+use tari_template_test_tooling::support::assert_error::{
+    assert_access_denied_for_action, assert_reject_reason,
+};
+use tari_template_test_tooling::transaction::{args, Transaction};
+use tari_template_test_tooling::TemplateTest;
+
+const TEMPLATE_MODULE_NAME: &str = "{{ project-name | upper_camel_case }}Pool";
+const POOL_FEE: u16 = 3;
+const PRICE_SCALE: u64 = 1_000_000_000_000;
+
+#[test]
+fn it_swaps_with_concentrated_liquidity() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, 990, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .call_method(pool_component, "get_price", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // a constant product pool with the same tokens would only return 987 B
+    let balances = result.finalize.execution_results[5]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    let output_amount = Amount::from(100_000u64) - balances[&b_resource];
+    assert!(output_amount > 990 && output_amount < 997);
+    assert_eq!(balances[&a_resource], 101_000);
+
+    // selling A makes it cheaper
+    let price = result.finalize.execution_results[6]
+        .decode::<Amount>()
+        .unwrap();
+    assert!(price < PRICE_SCALE);
+}
+
+#[test]
+fn it_rejects_swaps_beyond_the_available_liquidity() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // all the B tokens in the price range are sold before the input is consumed
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 500_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Insufficient liquidity in the pool");
+}
+
+#[test]
+fn it_only_pays_fees_to_positions_in_range() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        position_resource,
+    } = setup();
+
+    // a second position above the current price, that only holds A tokens
+    mint_position(
+        &mut test,
+        pool_component,
+        account,
+        &account_proof,
+        &account_key,
+        (a_resource, b_resource),
+        (2 * PRICE_SCALE, 4 * PRICE_SCALE),
+    );
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    // the whole fee of 3 A goes to the position in range
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(0)])
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(1)])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let (_, a_fees, b_fees) = result.finalize.execution_results[0]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    assert_eq!(a_fees, 3);
+    assert_eq!(b_fees, 0);
+    let (_, a_fees, _) = result.finalize.execution_results[1]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    assert_eq!(a_fees, 0);
+
+    // the position NFT holds the same fees as the pool
+    let (_, a_fees, b_fees) = position_nft_data(&test, position_resource, 0);
+    assert_eq!(a_fees, 3);
+    assert_eq!(b_fees, 0);
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(
+                account,
+                "create_proof_by_non_fungible_ids",
+                args![position_resource, vec![NonFungibleId::from_u64(0)]],
+            )
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(pool_component, "collect_fees", args![Workspace("proof")])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(account, "deposit", args![Workspace("fees.0")])
+            .call_method(account, "deposit", args![Workspace("fees.1")])
+            .drop_all_proofs_in_workspace()
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(0)])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    let (_, a_fees, _) = result.finalize.execution_results[7]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    assert_eq!(a_fees, 0);
+
+    let (_, a_fees, _) = position_nft_data(&test, position_resource, 0);
+    assert_eq!(a_fees, 0);
+}
+
+#[test]
+fn it_swaps_across_positions() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // a narrow position around the current price
+    mint_position(
+        &mut test,
+        pool_component,
+        account,
+        &account_proof,
+        &account_key,
+        (a_resource, b_resource),
+        (PRICE_SCALE * 9 / 10, PRICE_SCALE * 11 / 10),
+    );
+
+    // the swap moves the price below the narrow position, so both positions earn fees
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 150_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_price", args![])
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(0)])
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(1)])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let price = result.finalize.execution_results[5]
+        .decode::<Amount>()
+        .unwrap();
+    assert!(price < PRICE_SCALE * 9 / 10);
+
+    let (_, wide_position_fees, _) = result.finalize.execution_results[6]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    let (_, narrow_position_fees, _) = result.finalize.execution_results[7]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    assert!(!wide_position_fees.is_zero());
+    assert!(!narrow_position_fees.is_zero());
+    assert!(wide_position_fees + narrow_position_fees <= 450);
+}
+
+#[test]
+fn it_increases_the_liquidity_of_positions() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        position_resource,
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(0)])
+            .call_method(account, "withdraw", args![a_resource, 100_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 100_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                account,
+                "create_proof_by_non_fungible_ids",
+                args![position_resource, vec![NonFungibleId::from_u64(0)]],
+            )
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                pool_component,
+                "increase_liquidity",
+                args![Workspace("proof"), Workspace("a"), Workspace("b")],
+            )
+            .put_last_instruction_output_on_workspace("refund")
+            .call_method(account, "deposit", args![Workspace("refund.0")])
+            .call_method(account, "deposit", args![Workspace("refund.1")])
+            .drop_all_proofs_in_workspace()
+            .call_method(pool_component, "get_position", args![NonFungibleId::from_u64(0)])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let (initial_liquidity, _, _) = result.finalize.execution_results[0]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    let (liquidity, _, _) = result.finalize.execution_results[12]
+        .decode::<(Amount, Amount, Amount)>()
+        .unwrap();
+    assert_eq!(liquidity, initial_liquidity + initial_liquidity);
+
+    let (nft_liquidity, _, _) = position_nft_data(&test, position_resource, 0);
+    assert_eq!(nft_liquidity, liquidity);
+}
+
+#[test]
+fn it_burns_positions() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        position_resource,
+    } = setup();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    // the only position gets back all the tokens in the pool, except for rounding leftovers
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![position_resource, 1])
+            .put_last_instruction_output_on_workspace("position")
+            .call_method(pool_component, "burn_position", args![Workspace("position")])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(account, "deposit", args![Workspace("tokens.0")])
+            .call_method(account, "deposit", args![Workspace("tokens.1")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .call_method(account, "balance", args![position_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let balances = result.finalize.execution_results[6]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert!(balances[&a_resource] <= 2);
+    assert!(balances[&b_resource] <= 2);
+
+    let position_balance = result.finalize.execution_results[7]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(position_balance, 0);
+}
+
+#[test]
+fn it_prevents_managing_positions_outside_the_pool() {
+    let TestSetup {
+        mut test,
+        account,
+        account_proof,
+        account_key,
+        position_resource,
+        ..
+    } = setup();
+    let template = test.get_template_address("TestTokens");
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_function(
+                template,
+                "mint_non_fungible",
+                args![position_resource, NonFungibleId::from_u64(99)],
+            )
+            .put_last_instruction_output_on_workspace("position")
+            .call_method(account, "deposit", args![Workspace("position")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    assert_access_denied_for_action(reason, ResourceAuthAction::Mint);
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![position_resource, 1])
+            .put_last_instruction_output_on_workspace("position")
+            .call_function(template, "burn", args![Workspace("position")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    assert_access_denied_for_action(reason, ResourceAuthAction::Burn);
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_function(
+                template,
+                "update_non_fungible_data",
+                args![position_resource, NonFungibleId::from_u64(0)],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    assert_access_denied_for_action(reason, ResourceAuthAction::UpdateNonFungibleData);
+}
+
+#[test]
+fn it_rejects_invalid_price_ranges() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 1_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                pool_component,
+                "mint_position",
+                args![Workspace("a"), Workspace("b"), 2 * PRICE_SCALE, PRICE_SCALE],
+            )
+            .put_last_instruction_output_on_workspace("position")
+            .call_method(account, "deposit", args![Workspace("position.0")])
+            .call_method(account, "deposit", args![Workspace("position.1")])
+            .call_method(account, "deposit", args![Workspace("position.2")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Invalid price range");
+}
+
+struct TestSetup {
+    test: TemplateTest,
+    pool_component: ComponentAddress,
+    account: ComponentAddress,
+    account_proof: NonFungibleAddress,
+    account_key: RistrettoSecretKey,
+    a_resource: ResourceAddress,
+    b_resource: ResourceAddress,
+    position_resource: ResourceAddress,
+}
+
+// Creates a pool with a price of 1, and a position with 100_000 tokens of each resource in the range [0.5, 2]
+fn setup() -> TestSetup {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (account, account_proof, account_key) = test.create_funded_account();
+
+    let a_resource = create_token(&mut test, account, &account_proof, &account_key, "A");
+    let b_resource = create_token(&mut test, account, &account_proof, &account_key, "B");
+
+    let template = test.get_template_address(TEMPLATE_MODULE_NAME);
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                template,
+                "new",
+                args![a_resource, b_resource, POOL_FEE, PRICE_SCALE],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let pool_component = result.finalize.execution_results[0]
+        .decode::<ComponentAddress>()
+        .unwrap();
+
+    let position_resource = mint_position(
+        &mut test,
+        pool_component,
+        account,
+        &account_proof,
+        &account_key,
+        (a_resource, b_resource),
+        (PRICE_SCALE / 2, 2 * PRICE_SCALE),
+    );
+
+    TestSetup {
+        test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        position_resource,
+    }
+}
+
+// Mints a position with up to 100_000 tokens of each resource, returning the position resource
+fn mint_position(
+    test: &mut TemplateTest,
+    pool_component: ComponentAddress,
+    account: ComponentAddress,
+    account_proof: &NonFungibleAddress,
+    account_key: &RistrettoSecretKey,
+    (a_resource, b_resource): (ResourceAddress, ResourceAddress),
+    (lower_price, upper_price): (u64, u64),
+) -> ResourceAddress {
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 100_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 100_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                pool_component,
+                "mint_position",
+                args![Workspace("a"), Workspace("b"), lower_price, upper_price],
+            )
+            .put_last_instruction_output_on_workspace("position")
+            .call_method(account, "deposit", args![Workspace("position.0")])
+            .call_method(account, "deposit", args![Workspace("position.1")])
+            .call_method(account, "deposit", args![Workspace("position.2")])
+            .call_method(pool_component, "get_position_resource", args![])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+
+    result.finalize.execution_results[9]
+        .decode::<ResourceAddress>()
+        .unwrap()
+}
+
+// reads the mutable data of a position NFT from the state store, as (liquidity, a_fees, b_fees)
+fn position_nft_data(
+    test: &TemplateTest,
+    position_resource: ResourceAddress,
+    id: u64,
+) -> (Amount, Amount, Amount) {
+    let address = NonFungibleAddress::new(position_resource, NonFungibleId::from_u64(id));
+    let substate = test
+        .read_only_state_store()
+        .get_substate(&SubstateId::NonFungible(address))
+        .unwrap();
+    substate
+        .substate_value()
+        .as_non_fungible()
+        .and_then(|container| container.contents())
+        .expect("position NFT not found")
+        .decode_mutable_data()
+        .unwrap()
+}
+
+fn create_token(
+    test: &mut TemplateTest,
+    account: ComponentAddress,
+    account_proof: &NonFungibleAddress,
+    account_key: &RistrettoSecretKey,
+    symbol: &str,
+) -> ResourceAddress {
+    let template = test.get_template_address("TestTokens");
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(template, "create", args![symbol, 1_000_000])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(account, "deposit", args![Workspace("tokens")])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+
+    result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap()
+}
@@ -0,0 +1,101 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    minicbor::Encode,
+    minicbor::Decode,
+    minicbor::CborLen,
+)]
+#[cbor(index_only)]
+pub enum AdminRole {
+    #[n(0)]
+    Minter,
+    #[n(1)]
+    Burner,
+    #[n(2)]
+    ComplianceOfficer,
+    #[n(3)]
+    LimitManager,
+    #[n(5)]
+    Auditor,
+    /// Can call every admin method, and is the only role that can manage other admins
+    #[n(4)]
+    SuperAdmin,
+}
+
+impl AdminRole {
+    /// The component methods that admins with the role can call, in addition to super-admins
+    pub fn methods(&self) -> &'static [&'static str] {
+        match self {
+            // the role needed for each supply change is checked when it is proposed and approved
+            AdminRole::Minter | AdminRole::Burner => &[
+                "propose_supply_change",
+                "approve_supply_change",
+                "execute_supply_change",
+                "get_supply_proposals",
+            ],
+            AdminRole::ComplianceOfficer => &[
+                "create_new_user",
+                "create_new_users",
+                "get_user_data",
+                "get_user_kyc",
+                "get_user_limits",
+                "blacklist_user",
+                "blacklist_users",
+                "remove_from_blacklist",
+                "set_user_kyc",
+                "recall_tokens",
+                // only super-admins can unpause
+                "pause",
+            ],
+            AdminRole::LimitManager => &[
+                "get_user_limits",
+                "set_user_exchange_limit",
+                "set_user_exchange_limits",
+                "set_user_wrapped_exchange_limit",
+                "set_default_exchange_limit",
+            ],
+            AdminRole::Auditor => &["publish_reserve_attestation"],
+            AdminRole::SuperAdmin => &[],
+        }
+    }
+}
+
+impl Display for AdminRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminRole::Minter => write!(f, "minter"),
+            AdminRole::Burner => write!(f, "burner"),
+            AdminRole::ComplianceOfficer => write!(f, "compliance_officer"),
+            AdminRole::LimitManager => write!(f, "limit_manager"),
+            AdminRole::Auditor => write!(f, "auditor"),
+            AdminRole::SuperAdmin => write!(f, "super_admin"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct AdminData {
+    #[n(0)]
+    pub employee_id: String,
+    #[n(1)]
+    pub roles: BTreeSet<AdminRole>,
+}
+
+pub fn join_roles(roles: &BTreeSet<AdminRole>) -> String {
+    roles
+        .iter()
+        .map(|role| role.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -1,18 +1,27 @@
+mod admin_data;
+mod reserve_attestation;
+mod supply_proposal;
 mod user_data;
 mod wrapped_exchange_token;
 
-use user_data::{UserData, UserId, UserMutableData};
+use admin_data::{join_roles, AdminData, AdminRole};
+use reserve_attestation::ReserveAttestation;
+use supply_proposal::{SupplyChange, SupplyProposal};
+use user_data::{KycData, UserData, UserId, UserLimits, UserMutableData};
 
 use tari_template_lib::prelude::*;
 
 #[template]
 mod template {
     use super::*;
-    use crate::wrapped_exchange_token::{ExchangeFee, WrappedExchangeToken};
-    use std::collections::BTreeSet;
+    use crate::wrapped_exchange_token::{ExchangeFee, WrappedExchangeToken, BASIS_POINTS};
+    use std::collections::{BTreeMap, BTreeSet};
     use tari_template_lib::engine;
+    use tari_template_lib::types::Hash32;
 
     const DEFAULT_WRAPPED_TOKEN_EXCHANGE_FEE: ExchangeFee = ExchangeFee::Fixed(amount!(5));
+    // supply changes cannot be made by a single admin, so they are blocked until a second admin can approve them
+    const DEFAULT_SUPPLY_APPROVAL_THRESHOLD: usize = 2;
 
     pub struct {{ project-name | upper_camel_case }} {
         token_vault: Vault,
@@ -21,38 +30,95 @@ mod template {
         blacklisted_users: Vault,
         wrapped_token: Option<WrappedExchangeToken>,
         total_supply: Amount,
+        fee_vault: Vault,
+        collected_fees: Amount,
+        blacklisted_accounts: BTreeSet<ComponentAddress>,
+        default_exchange_limit: Amount,
+        // number of epochs after which the exchange limits of users are reset
+        exchange_limit_period: u64,
+        // roles of each admin badge, which are also in the badge data, used to build the component access rules
+        admin_roles: BTreeMap<NonFungibleId, BTreeSet<AdminRole>>,
+        // badge that owns the component, only used by the component to update its access rules
+        owner_badge: Vault,
+        supply_proposals: BTreeMap<u64, SupplyProposal>,
+        next_supply_proposal_id: u64,
+        // number of distinct admin badges that must approve a supply change
+        supply_approval_threshold: usize,
+        // number of epochs between proposing a supply change and executing it
+        supply_change_delay: u64,
+        // while paused, users cannot exchange or transfer tokens
+        paused: bool,
+        // published reserve attestations, oldest first
+        reserve_attestations: Vec<ReserveAttestation>,
     }
 
     impl {{ project-name | upper_camel_case }} {
-        /// Instantiates a new stable coin component, returning a bucket containing an admin badge
+        /// Instantiates a new stable coin component, returning a bucket containing a super-admin badge
         pub fn instantiate(
             initial_token_supply: Amount,
             token_symbol: String,
             token_metadata: Metadata,
             view_key: RistrettoPublicKeyBytes,
             enable_wrapped_token: bool,
+            default_exchange_limit: Amount,
+            exchange_limit_period: u64,
         ) -> Bucket {
+            assert!(
+                default_exchange_limit.is_positive(),
+                "Exchange limit must be positive"
+            );
+            assert!(
+                exchange_limit_period > 0,
+                "Exchange limit period must be at least one epoch"
+            );
+
             let provider_name = token_metadata
                 .get("provider_name")
                 .filter(|v| !v.trim().is_empty())
                 .expect("provider_name metadata entry is required");
 
-            // Create admin badge resource
-            let admin_badge =
-                ResourceBuilder::non_fungible().initial_supply(Some(NonFungibleId::from_u64(0)));
+            let component_alloc = CallerContext::allocate_component_address(None);
+            // Only the component can mint, burn and recall, after checking the roles of the admin
+            let require_component = rule!(component(component_alloc.get_address()));
+
+            // Create admin badge resource, the first admin is a super-admin that can create other admins
+            let admin_id = NonFungibleId::from_u64(0);
+            let admin_roles = BTreeSet::from([AdminRole::SuperAdmin]);
+            let admin_data = AdminData {
+                employee_id: String::new(),
+                roles: admin_roles.clone(),
+            };
+            let admin_badge = ResourceBuilder::non_fungible()
+                .mintable(require_component.clone(), LOCKED)
+                .burnable(require_component.clone(), LOCKED)
+                .recallable(require_component.clone(), LOCKED)
+                .initial_supply_with_data([(admin_id.clone(), (&admin_data, &()))]);
 
             // Create admin access rules
             let admin_resource = admin_badge.resource_address();
-            let require_admin = rule!(resource(admin_resource));
+
+            // The component updates user data (e.g. exchange limits) when users call it
+            let require_admin_or_component = rule!(any_of(
+                resource(admin_resource),
+                component(component_alloc.get_address())
+            ));
+
+            // Create component owner badge
+            let owner_badge = ResourceBuilder::non_fungible()
+                .mintable(AccessRule::DenyAll, LOCKED)
+                .initial_supply(Some(NonFungibleId::from_u64(0)));
+            let owner_resource = owner_badge.resource_address();
 
             // Create user badge resource. OWNER lets the resource owner (this component,
-            // gated by the admin badge) update each rule later — swap to LOCKED if you want
+            // through its owner badge) update each rule later — swap to LOCKED if you want
             // the rules to be permanently fixed at creation.
             let user_auth_resource = ResourceBuilder::non_fungible()
                 .add_metadata("provider_name", provider_name.trim())
-                .depositable(require_admin.clone(), OWNER)
-                .recallable(require_admin.clone(), OWNER)
-                .update_non_fungible_data(require_admin.clone(), OWNER)
+                .with_owner_rule(OwnerRule::ByAccessRule(rule!(resource(owner_resource))))
+                .mintable(require_component.clone(), OWNER)
+                .depositable(require_admin_or_component.clone(), OWNER)
+                .recallable(require_component.clone(), OWNER)
+                .update_non_fungible_data(require_admin_or_component, OWNER)
                 .build();
 
             // Create user access rules
@@ -61,7 +127,6 @@ mod template {
                 resource(user_auth_resource)
             ));
 
-            let component_alloc = CallerContext::allocate_component_address(None);
             // Create tokens resource with initial supply
             let initial_supply_proof =
                 ConfidentialOutputStatement::mint_revealed(initial_token_supply);
@@ -70,11 +135,11 @@ mod template {
                 .with_token_symbol(&token_symbol)
                 // Access rules. OWNER lets the resource owner (this component) update each
                 // rule later — swap to LOCKED to make any given rule permanent.
-                .mintable(require_admin.clone(), OWNER)
-                .burnable(require_admin.clone(), OWNER)
+                .mintable(require_component.clone(), OWNER)
+                .burnable(require_component.clone(), OWNER)
                 .depositable(require_user.clone(), OWNER)
-                .withdrawable(require_user.clone(), OWNER)
-                .recallable(require_admin.clone(), OWNER)
+                .withdrawable(require_user, OWNER)
+                .recallable(require_component.clone(), OWNER)
                 .with_authorization_hook(component_alloc.get_address(), "authorize_user_deposit")
                 .with_view_key(view_key)
                 .initial_supply(initial_supply_proof);
@@ -85,8 +150,8 @@ mod template {
                     .with_metadata(token_metadata)
                     .with_token_symbol(format!("w{token_symbol}"))
                     // Access rules — see comment above on OWNER vs LOCKED.
-                    .mintable(require_admin.clone(), OWNER)
-                    .burnable(require_admin.clone(), OWNER)
+                    .mintable(require_component.clone(), OWNER)
+                    .burnable(require_component.clone(), OWNER)
                     .initial_supply(initial_token_supply);
 
                 Some(WrappedExchangeToken {
@@ -97,28 +162,40 @@ mod template {
                 None
             };
 
-            // Create component access rules
-            let component_access_rules = AccessRules::new()
-                .add_method_rule("total_supply", AccessRule::AllowAll)
-                .add_method_rule("exchange_stable_for_wrapped_tokens", require_user.clone())
-                .add_method_rule("exchange_wrapped_for_stable_tokens", require_user.clone())
-                .add_method_rule("authorize_user_deposit", AccessRule::AllowAll)
-                .default(require_admin);
-
             // Create component
-            let _component = Component::new(Self {
+            let token_resource = initial_tokens.resource_address();
+            let owner_badge = Vault::from_bucket(owner_badge);
+            // creating a vault requires deposit permission, which the component has as the owner of user badges
+            let blacklisted_users = owner_badge.authorize_with(|| Vault::new_empty(user_auth_resource));
+            let stable_coin = Self {
                 token_vault: Vault::from_bucket(initial_tokens),
                 user_auth_resource,
-                admin_auth_resource: admin_badge.resource_address(),
-                blacklisted_users: Vault::new_empty(user_auth_resource),
+                admin_auth_resource: admin_resource,
+                blacklisted_users,
                 wrapped_token,
                 total_supply: initial_token_supply,
-            })
-            .with_address_allocation(component_alloc)
-            .with_access_rules(component_access_rules)
-            // Access is controlled by anyone with an admin badge, there is no single owner
-            .with_owner_rule(OwnerRule::None)
-            .create();
+                fee_vault: Vault::new_empty(token_resource),
+                collected_fees: Amount::zero(),
+                blacklisted_accounts: BTreeSet::new(),
+                default_exchange_limit,
+                exchange_limit_period,
+                admin_roles: BTreeMap::from([(admin_id, admin_roles)]),
+                owner_badge,
+                supply_proposals: BTreeMap::new(),
+                next_supply_proposal_id: 0,
+                supply_approval_threshold: DEFAULT_SUPPLY_APPROVAL_THRESHOLD,
+                supply_change_delay: 0,
+                paused: false,
+                reserve_attestations: Vec::new(),
+            };
+            let component_access_rules = stable_coin.component_access_rules();
+            let _component = Component::new(stable_coin)
+                .with_address_allocation(component_alloc)
+                .with_access_rules(component_access_rules)
+                // Access is controlled by the roles of admin badges. The owner badge is kept by the component, so
+                // that it can update its access rules when admins are created or revoked
+                .with_owner_rule(OwnerRule::ByAccessRule(rule!(resource(owner_resource))))
+                .create();
 
             admin_badge
         }
@@ -129,10 +206,13 @@ mod template {
                     let Some(component_state) = caller.component_state() else {
                         panic!("deposit not permitted from static template function")
                     };
+                    self.assert_not_paused();
+                    let account_address = caller.component().unwrap();
                     info!(
                         "Authorizing deposit for user with component {}",
-                        caller.component().unwrap()
+                        account_address
                     );
+                    self.assert_account_not_blacklisted(account_address);
                     let user_account =
                         Account::from_value(component_state).expect("not called from an account");
                     let vault = user_account
@@ -144,51 +224,226 @@ mod template {
                         panic!("This account does not have permission to deposit");
                     }
                 }
+                ResourceAuthAction::Withdraw => {
+                    self.assert_not_paused();
+                    if let Some(account_address) = caller.component() {
+                        self.assert_account_not_blacklisted(account_address);
+                    }
+                }
                 _ => {
-                    // Withdraws etc are permitted as per normal resource access rules
+                    // Other actions are permitted as per normal resource access rules
                 }
             }
         }
 
-        /// Increase token supply by amount.
-        pub fn increase_supply(&mut self, amount: Amount) {
-            let proof = ConfidentialOutputStatement::mint_revealed(amount);
-            let new_tokens = self.token_vault_manager().mint_confidential(proof);
-            self.token_vault.deposit(new_tokens);
-            self.total_supply += amount;
+        /// Propose a change to the token supply, which the proposer approves. Returns the id of the proposal
+        pub fn propose_supply_change(&mut self, proof: Proof, change: SupplyChange) -> u64 {
+            assert!(
+                change.amount().is_positive(),
+                "Supply change amount must be positive"
+            );
+            let admin_id = self.admin_id_with_role(&proof, change.required_role());
 
-            if let Some(ref mut wrapped_token) = self.wrapped_token {
-                let new_tokens =
-                    wrapped_token.get_resource_manager().mint_fungible(amount);
-                wrapped_token.vault_mut().deposit(new_tokens);
-            }
+            let id = self.next_supply_proposal_id;
+            self.next_supply_proposal_id += 1;
+            emit_event(
+                "propose_supply_change",
+                [
+                    ("proposal_id", id.to_string()),
+                    ("change", change.to_string()),
+                    ("admin_id", admin_id.to_string()),
+                ],
+            );
+            self.supply_proposals.insert(
+                id,
+                SupplyProposal {
+                    change,
+                    proposer: admin_id.clone(),
+                    approvals: BTreeSet::from([admin_id]),
+                    executable_epoch: Consensus::current_epoch() + self.supply_change_delay,
+                },
+            );
+            id
+        }
 
-            emit_event("increase_supply", [("amount", amount.to_string())]);
+        pub fn approve_supply_change(&mut self, proof: Proof, proposal_id: u64) {
+            let change = self.get_supply_proposal(proposal_id).change.clone();
+            let admin_id = self.admin_id_with_role(&proof, change.required_role());
+            let proposal = self
+                .supply_proposals
+                .get_mut(&proposal_id)
+                .expect("Supply proposal not found");
+            assert!(
+                proposal.approvals.insert(admin_id.clone()),
+                "Admin has already approved the supply change"
+            );
+            emit_event(
+                "approve_supply_change",
+                [
+                    ("proposal_id", proposal_id.to_string()),
+                    ("admin_id", admin_id.to_string()),
+                ],
+            );
         }
 
-        /// Decrease token supply by amount.
-        pub fn decrease_supply(&mut self, amount: Amount) {
-            let proof = ConfidentialWithdrawProof::revealed_withdraw(amount);
+        /// Apply an approved supply change, once the delay since it was proposed has passed
+        pub fn execute_supply_change(&mut self, proposal_id: u64) {
+            let proposal = self.get_supply_proposal(proposal_id);
+            // approvals of admins that were revoked, or lost the role, do not count
+            let required_role = proposal.change.required_role();
+            let approvals = proposal
+                .approvals
+                .iter()
+                .filter(|id| self.admin_has_role(id, required_role))
+                .count();
+            assert!(
+                approvals >= self.supply_approval_threshold,
+                "Supply change has {approvals} of the {} required approvals",
+                self.supply_approval_threshold
+            );
+            assert!(
+                Consensus::current_epoch() >= proposal.executable_epoch,
+                "Supply change cannot be executed before epoch {}",
+                proposal.executable_epoch
+            );
 
-            let tokens = self.token_vault.withdraw_confidential(proof);
-            tokens.burn();
-            self.total_supply -= amount;
+            let proposal = self.supply_proposals.remove(&proposal_id).unwrap();
+            match proposal.change {
+                SupplyChange::Increase(amount) => self.increase_supply(amount),
+                SupplyChange::Decrease(amount) => self.decrease_supply(amount),
+            }
+            emit_event(
+                "execute_supply_change",
+                [
+                    ("proposal_id", proposal_id.to_string()),
+                    ("approvals", approvals.to_string()),
+                ],
+            );
+        }
 
-            if let Some(ref mut wrapped_token) = self.wrapped_token {
-                let wrapped_tokens = wrapped_token.vault_mut().withdraw(amount);
-                wrapped_tokens.burn();
+        pub fn cancel_supply_change(&mut self, proposal_id: u64) {
+            self.supply_proposals
+                .remove(&proposal_id)
+                .expect("Supply proposal not found");
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "cancel_supply_change",
+                [
+                    ("proposal_id", proposal_id.to_string()),
+                    ("admin", admin.to_string()),
+                ],
+            );
+        }
+
+        pub fn get_supply_proposals(&self) -> BTreeMap<u64, SupplyProposal> {
+            self.supply_proposals.clone()
+        }
+
+        /// Set how many distinct admin badges must approve supply changes, and how many epochs must pass
+        /// between proposing and executing them. There must be enough admins to approve both kinds of supply change
+        pub fn set_supply_approval_policy(&mut self, threshold: usize, delay: u64) {
+            assert!(threshold > 0, "At least one approval is required");
+            for role in [AdminRole::Minter, AdminRole::Burner] {
+                let approvers = self.supply_approver_count(role);
+                assert!(
+                    threshold <= approvers,
+                    "Supply approval threshold of {threshold} is greater than the {approvers} admins with the {role} role"
+                );
             }
+            self.supply_approval_threshold = threshold;
+            self.supply_change_delay = delay;
 
+            let admin = CallerContext::transaction_signer_public_key();
             emit_event(
-                "decrease_supply",
-                [("revealed_burn_amount", amount.to_string())],
+                "set_supply_approval_policy",
+                [
+                    ("threshold", threshold.to_string()),
+                    ("delay", delay.to_string()),
+                    ("admin", admin.to_string()),
+                ],
             );
         }
 
+        /// Halt all exchanges and transfers of the stable coin between accounts
+        pub fn pause(&mut self) {
+            assert!(!self.paused, "Stable coin is already paused");
+            self.paused = true;
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event("pause", [("admin", admin.to_string())]);
+        }
+
+        pub fn unpause(&mut self) {
+            assert!(self.paused, "Stable coin is not paused");
+            self.paused = false;
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event("unpause", [("admin", admin.to_string())]);
+        }
+
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
         pub fn total_supply(&self) -> Amount {
             self.total_supply
         }
 
+        /// Publish an auditor's attestation of the reserves backing the token supply
+        pub fn publish_reserve_attestation(
+            &mut self,
+            proof: Proof,
+            reserve_amount: Amount,
+            currency: String,
+            report_hash: Hash32,
+        ) {
+            assert!(!currency.trim().is_empty(), "Currency is required");
+            assert!(
+                report_hash != Hash32::zero(),
+                "The hash of the audit report is required"
+            );
+            let auditor = self.admin_id_with_role(&proof, AdminRole::Auditor);
+
+            let attestation = ReserveAttestation {
+                reserve_amount,
+                currency: currency.trim().to_string(),
+                report_hash,
+                epoch: Consensus::current_epoch(),
+                auditor,
+                signer: CallerContext::transaction_signer_public_key(),
+            };
+            emit_event(
+                "publish_reserve_attestation",
+                [
+                    ("reserve_amount", attestation.reserve_amount.to_string()),
+                    ("currency", attestation.currency.clone()),
+                    ("report_hash", attestation.report_hash.to_string()),
+                    ("epoch", attestation.epoch.to_string()),
+                    ("auditor", attestation.auditor.to_string()),
+                    ("signer", attestation.signer.to_string()),
+                ],
+            );
+            self.reserve_attestations.push(attestation);
+        }
+
+        pub fn latest_attestation(&self) -> Option<ReserveAttestation> {
+            self.reserve_attestations.last().cloned()
+        }
+
+        pub fn attestation_history(&self) -> Vec<ReserveAttestation> {
+            self.reserve_attestations.clone()
+        }
+
+        /// Reserves of the latest attestation relative to the current total supply, in basis points
+        pub fn collateralization_ratio(&self) -> Amount {
+            let attestation = self
+                .reserve_attestations
+                .last()
+                .expect("No reserve attestation has been published");
+            assert!(self.total_supply.is_positive(), "Total supply is zero");
+            attestation.reserve_amount * Amount::from(BASIS_POINTS) / self.total_supply
+        }
+
         pub fn withdraw(&mut self, amount: Amount) -> Bucket {
             let proof = ConfidentialWithdrawProof::revealed_withdraw(amount);
             let bucket = self.token_vault.withdraw_confidential(proof);
@@ -229,24 +484,29 @@ mod template {
                 "The bucket must contain some tokens"
             );
 
+            self.assert_not_paused();
             proof.assert_resource(self.user_auth_resource);
             let badges = proof.get_non_fungibles();
             assert_eq!(badges.len(), 1, "The proof must contain exactly one badge");
             let badge = badges.into_iter().next().unwrap();
-            let badge = self.user_badge_manager().get_non_fungible(&badge);
+            let mut badge = self.user_badge_manager().get_non_fungible(&badge);
             let user = badge.get_data::<UserData>();
-            let user_data = badge.get_mutable_data::<UserMutableData>();
+            let mut user_data = badge.get_mutable_data::<UserMutableData>();
+            assert!(!user_data.is_blacklisted, "User is blacklisted");
+            self.assert_account_not_blacklisted(&user.user_account);
+            assert!(
+                !user_data.kyc.is_expired(Consensus::current_epoch()),
+                "User KYC has expired"
+            );
 
             let amount = confidential_bucket.amount();
+            let period_start_epoch = self.current_exchange_period_start();
             assert!(
-                amount <= user_data.wrapped_exchange_limit,
+                amount <= user_data.remaining_exchange_limit(period_start_epoch),
                 "Exchange limit exceeded"
             );
-
-            self.set_user_wrapped_exchange_limit(
-                user.user_id,
-                user_data.wrapped_exchange_limit - amount,
-            );
+            user_data.record_exchange(amount, period_start_epoch);
+            badge.set_mutable_data(&user_data);
 
             let fee = self
                 .wrapped_token_mut()
@@ -257,6 +517,7 @@ mod template {
                 .expect("Insufficient funds to pay exchange fee");
 
             self.token_vault.deposit(confidential_bucket);
+            self.collect_fee(fee);
 
             let wrapped_tokens = self.wrapped_token_mut().vault_mut().withdraw(new_amount);
 
@@ -278,6 +539,7 @@ mod template {
             proof: Proof,
             wrapped_bucket: Bucket,
         ) -> Bucket {
+            self.assert_not_paused();
             proof.assert_resource(self.user_auth_resource);
 
             assert_eq!(
@@ -296,13 +558,28 @@ mod template {
             let badge = badges.into_iter().next().unwrap();
             let badge = self.user_badge_manager().get_non_fungible(&badge);
             let user = badge.get_data::<UserData>();
+            let user_data = badge.get_mutable_data::<UserMutableData>();
+            assert!(!user_data.is_blacklisted, "User is blacklisted");
+            self.assert_account_not_blacklisted(&user.user_account);
+            assert!(
+                !user_data.kyc.is_expired(Consensus::current_epoch()),
+                "User KYC has expired"
+            );
 
             let amount = wrapped_bucket.amount();
+            let fee = self
+                .wrapped_token_mut()
+                .exchange_fee()
+                .calculate_fee(amount);
+            let new_amount = amount
+                .checked_sub(fee)
+                .expect("Insufficient funds to pay exchange fee");
 
             self.wrapped_token_mut().vault_mut().deposit(wrapped_bucket);
+            self.collect_fee(fee);
 
             // TODO: we should be able to call withdraw on the confidential resource without creating a revealed proof
-            let withdraw = ConfidentialWithdrawProof::revealed_withdraw(amount);
+            let withdraw = ConfidentialWithdrawProof::revealed_withdraw(new_amount);
             let tokens = self.token_vault.withdraw_confidential(withdraw);
 
             emit_event(
@@ -310,13 +587,58 @@ mod template {
                 [
                     ("user_id", user.user_id.to_string()),
                     ("amount", amount.to_string()),
-                    ("fee", 0.to_string()),
+                    ("fee", fee.to_string()),
                 ],
             );
 
             tokens
         }
 
+        pub fn get_exchange_fee(&self) -> ExchangeFee {
+            self.wrapped_token
+                .as_ref()
+                .expect("Wrapped token is not enabled")
+                .exchange_fee()
+                .clone()
+        }
+
+        /// Replace the fee charged on exchanges between stable and wrapped tokens
+        pub fn set_exchange_fee(&mut self, exchange_fee: ExchangeFee) {
+            exchange_fee.validate();
+            let new_fee = exchange_fee.to_string();
+            let old_fee = self.wrapped_token_mut().set_exchange_fee(exchange_fee);
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "set_exchange_fee",
+                [
+                    ("old_fee", old_fee.to_string()),
+                    ("new_fee", new_fee),
+                    ("admin", admin.to_string()),
+                ],
+            );
+        }
+
+        /// Total of all exchange fees collected, including fees that have been withdrawn
+        pub fn collected_fees(&self) -> Amount {
+            self.collected_fees
+        }
+
+        pub fn withdraw_fees(&mut self, amount: Amount) -> Bucket {
+            let proof = ConfidentialWithdrawProof::revealed_withdraw(amount);
+            let bucket = self.fee_vault.withdraw_confidential(proof);
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "withdraw_fees",
+                [
+                    ("amount", amount.to_string()),
+                    ("admin", admin.to_string()),
+                ],
+            );
+            bucket
+        }
+
         pub fn recall_tokens(
             &mut self,
             user_id: UserId,
@@ -351,42 +673,135 @@ mod template {
             );
         }
 
-        pub fn create_new_admin(&mut self, employee_id: String) -> Bucket {
+        pub fn create_new_admin(&mut self, employee_id: String, roles: BTreeSet<AdminRole>) -> Bucket {
+            assert!(!roles.is_empty(), "An admin must have at least one role");
             let id = NonFungibleId::random();
-            emit_event("create_new_admin", [("admin_id", id.to_string())]);
-            let mut metadata = Metadata::new();
-            metadata.insert("employee_id", employee_id);
+            emit_event(
+                "create_new_admin",
+                [
+                    ("admin_id", id.to_string()),
+                    ("roles", join_roles(&roles)),
+                ],
+            );
+            let admin_data = AdminData {
+                employee_id,
+                roles: roles.clone(),
+            };
             let badge = ResourceManager::get(self.admin_auth_resource).mint_non_fungible(
-                id,
-                &metadata,
+                id.clone(),
+                &admin_data,
                 &(),
             );
+            self.admin_roles.insert(id, roles);
+            self.update_access_rules();
             badge
         }
 
+        /// Recall and burn an admin badge, removing all of its roles
+        pub fn revoke_admin(&mut self, vault_id: VaultId, admin_id: NonFungibleId) {
+            let roles = self
+                .admin_roles
+                .remove(&admin_id)
+                .expect("Admin badge not found");
+            assert!(
+                !roles.contains(&AdminRole::SuperAdmin)
+                    || self
+                        .admin_roles
+                        .values()
+                        .any(|roles| roles.contains(&AdminRole::SuperAdmin)),
+                "Cannot revoke the last super-admin"
+            );
+            // supply changes would be blocked if too few admins are left to approve them
+            for role in [AdminRole::Minter, AdminRole::Burner] {
+                if roles.contains(&role) || roles.contains(&AdminRole::SuperAdmin) {
+                    assert!(
+                        self.supply_approver_count(role) >= self.supply_approval_threshold,
+                        "Cannot revoke an admin needed to reach the {} required {role} approvals",
+                        self.supply_approval_threshold
+                    );
+                }
+            }
+
+            let manager = ResourceManager::get(self.admin_auth_resource);
+            let badge = manager.recall_non_fungible(vault_id, admin_id.clone());
+            badge.burn();
+            self.update_access_rules();
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "revoke_admin",
+                [
+                    ("admin_id", admin_id.to_string()),
+                    ("roles", join_roles(&roles)),
+                    ("admin", admin.to_string()),
+                ],
+            );
+        }
+
         pub fn create_new_user(
             &mut self,
             user_id: UserId,
             user_account: ComponentAddress,
+            kyc: KycData,
         ) -> Bucket {
-            // TODO: configurable?
-            const DEFAULT_EXCHANGE_LIMIT: Amount = amount!(1000);
+            self.mint_user_badge(user_id, user_account, self.default_exchange_limit, kyc)
+        }
 
-            let badge = self.user_badge_manager().mint_non_fungible(
-                user_id.into(),
-                &UserData {
-                    user_id,
-                    user_account,
-                    // TODO: real time not implemented
-                    created_at: 0,
-                },
-                &UserMutableData {
-                    is_blacklisted: false,
-                    wrapped_exchange_limit: DEFAULT_EXCHANGE_LIMIT,
-                },
+        /// Create many users at once, each with their own exchange limit. The badges are deposited directly into the
+        /// accounts of the users
+        pub fn create_new_users(&mut self, users: Vec<(UserId, ComponentAddress, Amount, KycData)>) {
+            let badges = users
+                .into_iter()
+                .map(|(user_id, user_account, exchange_limit, kyc)| {
+                    let badge = self.mint_user_badge(user_id, user_account, exchange_limit, kyc);
+                    (user_account, badge)
+                })
+                .collect::<Vec<_>>();
+
+            // The deposit rule set at creation allows the admins and the component, but the accounts deposit the
+            // badges in their own calls, where neither applies. The accounts of the batch are allowed for the
+            // deposits with a single update, after which the rule is set back to the one set at creation
+            let deposit_rule = rule!(any_of(
+                resource(self.admin_auth_resource),
+                component(CallerContext::current_component_address())
+            ));
+            let batch_rule = badges.iter().fold(deposit_rule.clone(), |rule, (user_account, _)| {
+                rule.or(rule!(component(*user_account)))
+            });
+            let manager = self.user_badge_manager();
+            self.owner_badge.authorize_with(|| {
+                manager.update_access_rule(ResourceAuthAction::Deposit, batch_rule);
+                for (user_account, badge) in badges {
+                    ComponentManager::get(user_account).invoke("deposit", args![badge]);
+                }
+                manager.update_access_rule(ResourceAuthAction::Deposit, deposit_rule);
+            });
+        }
+
+        /// Replace the KYC details of a user, e.g. when their verification is renewed
+        pub fn set_user_kyc(&mut self, user_id: UserId, kyc: KycData) {
+            kyc.validate(Consensus::current_epoch());
+            let mut badge = self.user_badge_manager().get_non_fungible(&user_id.into());
+            let mut user_data = badge.get_mutable_data::<UserMutableData>();
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "set_user_kyc",
+                [
+                    ("user_id", user_id.to_string()),
+                    ("kyc_tier", kyc.tier.to_string()),
+                    ("jurisdiction", kyc.jurisdiction.clone()),
+                    ("kyc_expiry_epoch", kyc.expiry_epoch.to_string()),
+                    ("admin", admin.to_string()),
+                ],
             );
-            emit_event("create_new_user", [("user_id", user_id.to_string())]);
-            badge
+            user_data.kyc = kyc;
+            badge.set_mutable_data(&user_data);
+        }
+
+        pub fn get_user_kyc(&self, user_id: UserId) -> KycData {
+            let badge = self.user_badge_manager().get_non_fungible(&user_id.into());
+            badge.get_mutable_data::<UserMutableData>().kyc
         }
 
         pub fn set_user_exchange_limit(&mut self, user_id: UserId, limit: Amount) {
@@ -415,12 +830,42 @@ mod template {
             );
         }
 
+        pub fn set_user_exchange_limits(&mut self, limits: Vec<(UserId, Amount)>) {
+            for (user_id, limit) in limits {
+                self.set_user_exchange_limit(user_id, limit);
+            }
+        }
+
+        /// Set the exchange limit given to new users, the limits of existing users are not changed
+        pub fn set_default_exchange_limit(&mut self, limit: Amount) {
+            assert!(limit.is_positive(), "Exchange limit must be positive");
+            self.default_exchange_limit = limit;
+
+            let admin = CallerContext::transaction_signer_public_key();
+            emit_event(
+                "set_default_exchange_limit",
+                [("limit", limit.to_string()), ("admin", admin.to_string())],
+            );
+        }
+
+        pub fn get_user_limits(&self, user_id: UserId) -> UserLimits {
+            let badge = self.user_badge_manager().get_non_fungible(&user_id.into());
+            let user_data = badge.get_mutable_data::<UserMutableData>();
+            let period_start_epoch = self.current_exchange_period_start();
+            UserLimits {
+                wrapped_exchange_limit: user_data.wrapped_exchange_limit,
+                remaining_exchange_limit: user_data.remaining_exchange_limit(period_start_epoch),
+                reset_epoch: period_start_epoch + self.exchange_limit_period,
+            }
+        }
+
         pub fn blacklist_user(&mut self, vault_id: VaultId, user_id: UserId) {
             let non_fungible_id: NonFungibleId = user_id.into();
 
             let manager = self.user_badge_manager();
             let recalled = manager.recall_non_fungible(vault_id, non_fungible_id.clone());
             let user_badge = manager.get_non_fungible(&non_fungible_id);
+            let user = user_badge.get_data::<UserData>();
             let user_data = user_badge.get_mutable_data::<UserMutableData>();
             manager.update_non_fungible_data(
                 non_fungible_id,
@@ -431,9 +876,16 @@ mod template {
             );
 
             self.blacklisted_users.deposit(recalled);
+            self.blacklisted_accounts.insert(user.user_account);
             emit_event("blacklist_user", [("user_id", user_id.to_string())]);
         }
 
+        pub fn blacklist_users(&mut self, users: Vec<(VaultId, UserId)>) {
+            for (vault_id, user_id) in users {
+                self.blacklist_user(vault_id, user_id);
+            }
+        }
+
         pub fn remove_from_blacklist(&mut self, user_id: UserId) -> Bucket {
             let non_fungible_id: NonFungibleId = user_id.into();
             let user_badge_bucket = self
@@ -441,6 +893,7 @@ mod template {
                 .withdraw_non_fungible(non_fungible_id.clone());
             let manager = self.user_badge_manager();
             let user_badge = manager.get_non_fungible(&non_fungible_id);
+            let user = user_badge.get_data::<UserData>();
             let user_data = user_badge.get_mutable_data::<UserMutableData>();
             manager.update_non_fungible_data(
                 non_fungible_id,
@@ -449,6 +902,7 @@ mod template {
                     ..user_data
                 },
             );
+            self.blacklisted_accounts.remove(&user.user_account);
             emit_event("remove_from_blacklist", [("user_id", user_id.to_string())]);
             user_badge_bucket
         }
@@ -472,6 +926,206 @@ mod template {
             );
         }
 
+        fn mint_user_badge(
+            &self,
+            user_id: UserId,
+            user_account: ComponentAddress,
+            exchange_limit: Amount,
+            kyc: KycData,
+        ) -> Bucket {
+            assert!(
+                exchange_limit.is_positive(),
+                "Exchange limit must be positive"
+            );
+            let current_epoch = Consensus::current_epoch();
+            kyc.validate(current_epoch);
+            emit_event(
+                "create_new_user",
+                [
+                    ("user_id", user_id.to_string()),
+                    ("exchange_limit", exchange_limit.to_string()),
+                    ("kyc_tier", kyc.tier.to_string()),
+                    ("jurisdiction", kyc.jurisdiction.clone()),
+                    ("kyc_expiry_epoch", kyc.expiry_epoch.to_string()),
+                ],
+            );
+            self.user_badge_manager().mint_non_fungible(
+                user_id.into(),
+                &UserData {
+                    user_id,
+                    user_account,
+                    created_at: current_epoch,
+                },
+                &UserMutableData {
+                    is_blacklisted: false,
+                    wrapped_exchange_limit: exchange_limit,
+                    exchanged_in_period: Amount::zero(),
+                    period_start_epoch: 0,
+                    kyc,
+                },
+            )
+        }
+
+        fn increase_supply(&mut self, amount: Amount) {
+            let proof = ConfidentialOutputStatement::mint_revealed(amount);
+            let new_tokens = self.token_vault_manager().mint_confidential(proof);
+            self.token_vault.deposit(new_tokens);
+            self.total_supply += amount;
+
+            if let Some(ref mut wrapped_token) = self.wrapped_token {
+                let new_tokens =
+                    wrapped_token.get_resource_manager().mint_fungible(amount);
+                wrapped_token.vault_mut().deposit(new_tokens);
+            }
+
+            emit_event("increase_supply", [("amount", amount.to_string())]);
+        }
+
+        fn decrease_supply(&mut self, amount: Amount) {
+            let proof = ConfidentialWithdrawProof::revealed_withdraw(amount);
+
+            let tokens = self.token_vault.withdraw_confidential(proof);
+            tokens.burn();
+            self.total_supply -= amount;
+
+            if let Some(ref mut wrapped_token) = self.wrapped_token {
+                let wrapped_tokens = wrapped_token.vault_mut().withdraw(amount);
+                wrapped_tokens.burn();
+            }
+
+            emit_event(
+                "decrease_supply",
+                [("revealed_burn_amount", amount.to_string())],
+            );
+        }
+
+        fn get_supply_proposal(&self, proposal_id: u64) -> &SupplyProposal {
+            self.supply_proposals
+                .get(&proposal_id)
+                .expect("Supply proposal not found")
+        }
+
+        // the admin badge in the proof, which must have the role or be a super-admin
+        fn admin_id_with_role(&self, proof: &Proof, role: AdminRole) -> NonFungibleId {
+            proof.assert_resource(self.admin_auth_resource);
+            let badges = proof.get_non_fungibles();
+            assert_eq!(badges.len(), 1, "The proof must contain exactly one badge");
+            let admin_id = badges.into_iter().next().unwrap();
+            assert!(
+                self.admin_has_role(&admin_id, role),
+                "Admin does not have the {role} role"
+            );
+            admin_id
+        }
+
+        fn admin_has_role(&self, admin_id: &NonFungibleId, role: AdminRole) -> bool {
+            self.admin_roles.get(admin_id).is_some_and(|roles| {
+                roles.contains(&role) || roles.contains(&AdminRole::SuperAdmin)
+            })
+        }
+
+        fn supply_approver_count(&self, role: AdminRole) -> usize {
+            self.admin_roles
+                .keys()
+                .filter(|id| self.admin_has_role(id, role))
+                .count()
+        }
+
+        fn component_access_rules(&self) -> AccessRules {
+            let require_user = rule!(any_of(
+                resource(self.admin_auth_resource),
+                resource(self.user_auth_resource)
+            ));
+
+            let mut access_rules = AccessRules::new()
+                .add_method_rule("total_supply", AccessRule::AllowAll)
+                .add_method_rule("get_exchange_fee", AccessRule::AllowAll)
+                .add_method_rule("collected_fees", AccessRule::AllowAll)
+                .add_method_rule("is_paused", AccessRule::AllowAll)
+                .add_method_rule("latest_attestation", AccessRule::AllowAll)
+                .add_method_rule("attestation_history", AccessRule::AllowAll)
+                .add_method_rule("collateralization_ratio", AccessRule::AllowAll)
+                .add_method_rule("exchange_stable_for_wrapped_tokens", require_user.clone())
+                .add_method_rule("exchange_wrapped_for_stable_tokens", require_user)
+                .add_method_rule("authorize_user_deposit", AccessRule::AllowAll)
+                .default(self.require_admins_with_roles(&[]));
+
+            let mut method_roles = BTreeMap::<&str, Vec<AdminRole>>::new();
+            for role in [
+                AdminRole::Minter,
+                AdminRole::Burner,
+                AdminRole::ComplianceOfficer,
+                AdminRole::LimitManager,
+                AdminRole::Auditor,
+            ] {
+                for method in role.methods() {
+                    method_roles.entry(method).or_default().push(role);
+                }
+            }
+            for (method, roles) in method_roles {
+                access_rules =
+                    access_rules.add_method_rule(method, self.require_admins_with_roles(&roles));
+            }
+            access_rules
+        }
+
+        // any of the admin badges with one of the roles, or with the super-admin role
+        fn require_admins_with_roles(&self, roles: &[AdminRole]) -> AccessRule {
+            let badges = self
+                .admin_roles
+                .iter()
+                .filter(|(_, admin_roles)| {
+                    admin_roles.contains(&AdminRole::SuperAdmin)
+                        || roles.iter().any(|role| admin_roles.contains(role))
+                })
+                .map(|(id, _)| {
+                    RuleRequirement::NonFungibleAddress(NonFungibleAddress::new(
+                        self.admin_auth_resource,
+                        id.clone(),
+                    ))
+                })
+                .collect::<Vec<_>>();
+            AccessRule::Restricted(RestrictedAccessRule::Require(RequireRule::AnyOf(
+                badges.into_boxed_slice(),
+            )))
+        }
+
+        fn update_access_rules(&self) {
+            let access_rules = self.component_access_rules();
+            self.owner_badge
+                .authorize_with(|| ComponentManager::current().set_access_rules(access_rules));
+        }
+
+        // exchange limit periods start at multiples of the period length
+        fn current_exchange_period_start(&self) -> u64 {
+            let epoch = Consensus::current_epoch();
+            epoch - epoch % self.exchange_limit_period
+        }
+
+        // the authorization hook cannot read the data of locked badges (e.g. while a proof of them exists), so the
+        // accounts of blacklisted users are also tracked by the component
+        fn assert_account_not_blacklisted(&self, account_address: &ComponentAddress) {
+            assert!(
+                !self.blacklisted_accounts.contains(account_address),
+                "This account is blacklisted"
+            );
+        }
+
+        fn assert_not_paused(&self) {
+            assert!(!self.paused, "Stable coin is paused");
+        }
+
+        // exchange fees are paid in stable tokens, which are moved from the reserves to the fee vault
+        fn collect_fee(&mut self, fee: Amount) {
+            if fee.is_zero() {
+                return;
+            }
+            let proof = ConfidentialWithdrawProof::revealed_withdraw(fee);
+            let fee_tokens = self.token_vault.withdraw_confidential(proof);
+            self.fee_vault.deposit(fee_tokens);
+            self.collected_fees += fee;
+        }
+
         fn user_badge_manager(&self) -> ResourceManager {
             ResourceManager::get(self.user_auth_resource)
         }
@@ -0,0 +1,27 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+use tari_template_lib::types::crypto::RistrettoPublicKeyBytes;
+use tari_template_lib::types::{Amount, Hash32, NonFungibleId};
+
+/// An auditor's report of the reserves backing the stable coin, signed by the auditor as the transaction signer
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct ReserveAttestation {
+    #[n(0)]
+    pub reserve_amount: Amount,
+    /// Currency of the reserves (e.g. "USD")
+    #[n(1)]
+    pub currency: String,
+    /// Hash of the off-chain audit report
+    #[n(2)]
+    pub report_hash: Hash32,
+    /// Epoch in which the attestation was published
+    #[n(3)]
+    pub epoch: u64,
+    /// Admin badge of the auditor that published the attestation
+    #[n(4)]
+    pub auditor: NonFungibleId,
+    /// Public key of the auditor that signed the transaction publishing the attestation
+    #[n(5)]
+    pub signer: RistrettoPublicKeyBytes,
+}
@@ -0,0 +1,56 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use tari_template_lib::types::{Amount, NonFungibleId};
+
+use crate::admin_data::AdminRole;
+
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub enum SupplyChange {
+    #[n(0)]
+    Increase(#[n(0)] Amount),
+    #[n(1)]
+    Decrease(#[n(0)] Amount),
+}
+
+impl SupplyChange {
+    pub fn amount(&self) -> Amount {
+        match self {
+            SupplyChange::Increase(amount) | SupplyChange::Decrease(amount) => *amount,
+        }
+    }
+
+    /// The role that admins need to propose and approve the change, in addition to super-admins
+    pub fn required_role(&self) -> AdminRole {
+        match self {
+            SupplyChange::Increase(_) => AdminRole::Minter,
+            SupplyChange::Decrease(_) => AdminRole::Burner,
+        }
+    }
+}
+
+impl Display for SupplyChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SupplyChange::Increase(amount) => write!(f, "increase({amount})"),
+            SupplyChange::Decrease(amount) => write!(f, "decrease({amount})"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct SupplyProposal {
+    #[n(0)]
+    pub change: SupplyChange,
+    #[n(1)]
+    pub proposer: NonFungibleId,
+    /// Admin badges that approved the change, including the proposer
+    #[n(2)]
+    pub approvals: BTreeSet<NonFungibleId>,
+    /// The first epoch in which the change can be executed
+    #[n(3)]
+    pub executable_epoch: u64,
+}
@@ -52,16 +52,55 @@ pub struct UserData {
     pub user_id: UserId,
     #[n(1)]
     pub user_account: ComponentAddress,
+    /// Epoch in which the user was created
     #[n(2)]
     pub created_at: u64,
 }
 
+#[derive(Clone, Debug, Default, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct KycData {
+    #[n(0)]
+    pub tier: u8,
+    /// ISO 3166-1 alpha-2 code of the country the user was verified in (e.g. "GB")
+    #[n(1)]
+    pub jurisdiction: String,
+    /// The first epoch in which the verification is no longer valid
+    #[n(2)]
+    pub expiry_epoch: u64,
+}
+
+impl KycData {
+    pub fn validate(&self, current_epoch: u64) {
+        assert!(
+            self.jurisdiction.len() == 2
+                && self.jurisdiction.chars().all(|c| c.is_ascii_uppercase()),
+            "Jurisdiction must be a two-letter country code"
+        );
+        assert!(
+            self.expiry_epoch > current_epoch,
+            "KYC expiry epoch must be in the future"
+        );
+    }
+
+    pub fn is_expired(&self, current_epoch: u64) -> bool {
+        current_epoch >= self.expiry_epoch
+    }
+}
+
 #[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
 pub struct UserMutableData {
     #[n(0)]
     pub is_blacklisted: bool,
+    /// The amount that can be exchanged for wrapped tokens in each exchange limit period
     #[n(1)]
     pub wrapped_exchange_limit: Amount,
+    /// The amount exchanged for wrapped tokens in the period starting at "period_start_epoch"
+    #[n(2)]
+    pub exchanged_in_period: Amount,
+    #[n(3)]
+    pub period_start_epoch: u64,
+    #[n(4)]
+    pub kyc: KycData,
 }
 
 impl UserMutableData {
@@ -69,6 +108,25 @@ impl UserMutableData {
         self.wrapped_exchange_limit = limit;
         self
     }
+
+    pub fn remaining_exchange_limit(&self, period_start_epoch: u64) -> Amount {
+        if self.period_start_epoch != period_start_epoch {
+            return self.wrapped_exchange_limit;
+        }
+        // the limit may have been lowered below the amount already exchanged
+        self.wrapped_exchange_limit
+            .checked_sub(self.exchanged_in_period)
+            .unwrap_or_default()
+    }
+
+    pub fn record_exchange(&mut self, amount: Amount, period_start_epoch: u64) -> &mut Self {
+        if self.period_start_epoch != period_start_epoch {
+            self.period_start_epoch = period_start_epoch;
+            self.exchanged_in_period = Amount::zero();
+        }
+        self.exchanged_in_period += amount;
+        self
+    }
 }
 
 impl Default for UserMutableData {
@@ -76,6 +134,20 @@ impl Default for UserMutableData {
         Self {
             is_blacklisted: false,
             wrapped_exchange_limit: 1000u64.into(),
+            exchanged_in_period: Amount::zero(),
+            period_start_epoch: 0,
+            kyc: KycData::default(),
         }
     }
 }
+
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct UserLimits {
+    #[n(0)]
+    pub wrapped_exchange_limit: Amount,
+    #[n(1)]
+    pub remaining_exchange_limit: Amount,
+    /// The epoch in which the remaining exchange limit is reset to the full limit
+    #[n(2)]
+    pub reset_epoch: u64,
+}
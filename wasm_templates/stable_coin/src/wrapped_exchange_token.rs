@@ -1,25 +1,121 @@
 // Copyright 2024 The Tari Project
 // SPDX-License-Identifier: BSD-3-Clause
 
+use std::fmt;
+
 use tari_template_lib::models::Vault;
-use tari_template_lib::types::Amount;
 use tari_template_lib::resource::ResourceManager;
+use tari_template_lib::types::Amount;
 
-#[derive(Debug, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+// fees in basis points are relative to this value (e.g. "25" represents "0.25%")
+pub const BASIS_POINTS: u16 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
 pub enum ExchangeFee {
+    /// The same fee for every exchange, regardless of the amount
     #[n(0)]
     Fixed(#[n(0)] Amount),
+    /// A share of the exchanged amount, in basis points
+    #[n(1)]
+    BasisPoints(#[n(0)] u16),
+    /// A share of the exchanged amount, in basis points, that is never lower than "min" or higher than "max"
+    #[n(2)]
+    Capped {
+        #[n(0)]
+        basis_points: u16,
+        #[n(1)]
+        min: Amount,
+        #[n(2)]
+        max: Amount,
+    },
+    /// A share of the exchanged amount that depends on the size of the exchange. Like income tax brackets, each
+    /// tier's rate only applies to the part of the amount between its threshold and the next one
+    #[n(3)]
+    Tiered(#[n(0)] Vec<FeeTier>),
+}
+
+#[derive(Debug, Clone, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct FeeTier {
+    #[n(0)]
+    pub threshold: Amount,
     #[n(1)]
-    Percentage(#[n(0)] u64),
+    pub basis_points: u16,
 }
 
 impl ExchangeFee {
+    pub fn validate(&self) {
+        match self {
+            ExchangeFee::Fixed(_) => {}
+            ExchangeFee::BasisPoints(basis_points) => validate_basis_points(*basis_points),
+            ExchangeFee::Capped {
+                basis_points,
+                min,
+                max,
+            } => {
+                validate_basis_points(*basis_points);
+                // a zero cap would waive the fee, which is what a zero fixed fee is for
+                assert!(max.is_positive(), "Maximum exchange fee must be positive");
+                assert!(min <= max, "Minimum exchange fee cannot be greater than the maximum");
+            }
+            ExchangeFee::Tiered(tiers) => {
+                let first = tiers.first().expect("Tiered exchange fee must have at least one tier");
+                assert!(first.threshold.is_zero(), "The first fee tier must start at zero");
+                for tier in tiers {
+                    validate_basis_points(tier.basis_points);
+                }
+                assert!(
+                    tiers.windows(2).all(|w| w[0].threshold < w[1].threshold),
+                    "Fee tier thresholds must be in increasing order"
+                );
+            }
+        }
+    }
+
     pub fn calculate_fee(&self, amount: Amount) -> Amount {
         match self {
             ExchangeFee::Fixed(fee) => *fee,
-            ExchangeFee::Percentage(percentage) => {
-                let inv_perc = 100 / *percentage;
-                div_rounded(amount, inv_perc.into()).try_into().unwrap()
+            ExchangeFee::BasisPoints(basis_points) => basis_points_of(amount, *basis_points),
+            ExchangeFee::Capped {
+                basis_points,
+                min,
+                max,
+            } => basis_points_of(amount, *basis_points).max(*min).min(*max),
+            ExchangeFee::Tiered(tiers) => {
+                // sum the unrounded fee of every tier the amount reaches, so the fee never decreases as the amount
+                // grows
+                let mut scaled_fee = Amount::zero();
+                for (i, tier) in tiers.iter().enumerate() {
+                    if amount <= tier.threshold {
+                        break;
+                    }
+                    let tier_end = tiers.get(i + 1).map_or(amount, |next| next.threshold.min(amount));
+                    scaled_fee += (tier_end - tier.threshold) * Amount::from(tier.basis_points);
+                }
+                round_basis_points(scaled_fee)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExchangeFee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeFee::Fixed(fee) => write!(f, "fixed({fee})"),
+            ExchangeFee::BasisPoints(basis_points) => write!(f, "basis_points({basis_points})"),
+            ExchangeFee::Capped {
+                basis_points,
+                min,
+                max,
+            } => write!(f, "capped({basis_points}, min: {min}, max: {max})"),
+            ExchangeFee::Tiered(tiers) => {
+                write!(f, "tiered(")?;
+                for (i, tier) in tiers.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", tier.threshold, tier.basis_points)?;
+                }
+                write!(f, ")")
             }
         }
     }
@@ -50,34 +146,126 @@ impl WrappedExchangeToken {
     pub fn exchange_fee(&self) -> &ExchangeFee {
         &self.exchange_fee
     }
+
+    pub fn set_exchange_fee(&mut self, exchange_fee: ExchangeFee) -> ExchangeFee {
+        std::mem::replace(&mut self.exchange_fee, exchange_fee)
+    }
+}
+
+fn validate_basis_points(basis_points: u16) {
+    assert!(
+        basis_points <= BASIS_POINTS,
+        "Exchange fee cannot be greater than {BASIS_POINTS} basis points"
+    );
 }
 
-fn div_rounded<A: Into<Amount>>(v: A, p: A) -> Amount {
-    let v = v.into();
-    let p = p.into();
-    // f and b are the division to 3 decimals
-    let f = (v * Amount::ONE_THOUSAND) * p / Amount::ONE_HUNDRED;
-    let b = v * p / Amount::ONE_HUNDRED;
-    let c = f - (b * Amount::ONE_THOUSAND);
+// the share of the amount in basis points, rounded half up
+fn basis_points_of(amount: Amount, basis_points: u16) -> Amount {
+    round_basis_points(amount * Amount::from(basis_points))
+}
 
-    // If the decimal is greater or equal to 0.5, we round up
-    if c >= 500 {
-        (f / Amount::ONE_THOUSAND) + Amount::ONE
-    } else {
-        b
-    }
+// converts an amount scaled by basis points back to the unscaled amount, rounded half up
+fn round_basis_points(scaled: Amount) -> Amount {
+    let scale = Amount::from(BASIS_POINTS);
+    (scaled + scale / 2) / scale
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn tier(threshold: u64, basis_points: u16) -> FeeTier {
+        FeeTier {
+            threshold: threshold.into(),
+            basis_points,
+        }
+    }
+
+    #[test]
+    fn test_basis_points_of() {
+        assert_eq!(basis_points_of(0u64.into(), 500), 0);
+        assert_eq!(basis_points_of(100u64.into(), 0), 0);
+        assert_eq!(basis_points_of(100u64.into(), 500), 5);
+        assert_eq!(basis_points_of(123u64.into(), 500), 6);
+        assert_eq!(basis_points_of(130u64.into(), 500), 7);
+        assert_eq!(basis_points_of(1000u64.into(), 25), 3);
+        assert_eq!(basis_points_of(1000u64.into(), 10_000), 1000);
+    }
+
+    #[test]
+    fn test_calculate_fee() {
+        let amount = Amount::from(10_000u64);
+        assert_eq!(ExchangeFee::Fixed(5u64.into()).calculate_fee(amount), 5);
+        assert_eq!(ExchangeFee::BasisPoints(30).calculate_fee(amount), 30);
+
+        let capped = ExchangeFee::Capped {
+            basis_points: 100,
+            min: 20u64.into(),
+            max: 500u64.into(),
+        };
+        assert_eq!(capped.calculate_fee(1_000u64.into()), 20);
+        assert_eq!(capped.calculate_fee(amount), 100);
+        assert_eq!(capped.calculate_fee(100_000u64.into()), 500);
+
+        let tiered = ExchangeFee::Tiered(vec![tier(0, 100), tier(1_000, 50), tier(10_000, 10)]);
+        assert_eq!(tiered.calculate_fee(999u64.into()), 10);
+        assert_eq!(tiered.calculate_fee(1_000u64.into()), 10);
+        // 1% of the first 1_000 and 0.5% of the next 9_000
+        assert_eq!(tiered.calculate_fee(amount), 55);
+        // plus 0.1% of the 90_000 above the last threshold
+        assert_eq!(tiered.calculate_fee(100_000u64.into()), 145);
+    }
+
+    #[test]
+    fn test_tiered_fee_does_not_decrease_at_tier_boundaries() {
+        let tiered = ExchangeFee::Tiered(vec![tier(0, 250), tier(500, 100), tier(2_000, 0)]);
+        assert_eq!(tiered.calculate_fee(0u64.into()), 0);
+        // 12.5 rounds up to 13, and the next token only adds 0.01
+        assert_eq!(tiered.calculate_fee(500u64.into()), 13);
+        assert_eq!(tiered.calculate_fee(501u64.into()), 13);
+        // 12.5 + 15 rounds up to 28, and the zero rate tier adds nothing
+        assert_eq!(tiered.calculate_fee(2_000u64.into()), 28);
+        assert_eq!(tiered.calculate_fee(1_000_000u64.into()), 28);
+
+        let mut previous = Amount::zero();
+        for amount in 0..2_100u64 {
+            let fee = tiered.calculate_fee(amount.into());
+            assert!(fee >= previous, "Fee decreased from {previous} to {fee} at {amount}");
+            previous = fee;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Exchange fee cannot be greater than 10000 basis points")]
+    fn test_validate_rejects_too_many_basis_points() {
+        ExchangeFee::BasisPoints(10_001).validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum exchange fee cannot be greater than the maximum")]
+    fn test_validate_rejects_inverted_caps() {
+        ExchangeFee::Capped {
+            basis_points: 100,
+            min: 10u64.into(),
+            max: 5u64.into(),
+        }
+        .validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Maximum exchange fee must be positive")]
+    fn test_validate_rejects_zero_cap() {
+        ExchangeFee::Capped {
+            basis_points: 100,
+            min: 0u64.into(),
+            max: 0u64.into(),
+        }
+        .validate();
+    }
+
     #[test]
-    fn test_div_rounded() {
-        assert_eq!(div_rounded(0u64, 5), 0);
-        assert_eq!(div_rounded(100u64, 0), 0);
-        assert_eq!(div_rounded(100u64, 5), 5);
-        assert_eq!(div_rounded(123u64, 5), 6);
-        assert_eq!(div_rounded(130u64, 5), 7);
+    #[should_panic(expected = "Fee tier thresholds must be in increasing order")]
+    fn test_validate_rejects_unordered_tiers() {
+        ExchangeFee::Tiered(vec![tier(0, 100), tier(1_000, 50), tier(1_000, 10)]).validate();
     }
 }
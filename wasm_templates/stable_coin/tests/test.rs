@@ -1,15 +1,28 @@
-use tari_template_lib::types::{ComponentAddress, Metadata, NonFungibleAddress, ResourceAddress};
-use tari_template_lib::types::Amount;
-use tari_template_test_tooling::crypto::RistrettoSecretKey;
+use tari_template_lib::types::{
+    ComponentAddress, Metadata, NonFungibleAddress, NonFungibleId, ResourceAddress, VaultId,
+};
+use std::collections::BTreeSet;
+use tari_template_lib::prelude::{rule, ResourceAuthAction};
+use tari_template_lib::types::crypto::RistrettoPublicKeyBytes;
+use tari_template_lib::types::{Amount, Hash32};
+use tari_template_test_tooling::crypto::{PublicKey, RistrettoPublicKey, RistrettoSecretKey};
 use tari_template_test_tooling::support::assert_error::assert_reject_reason;
 use tari_template_test_tooling::TemplateTest;
 use tari_template_test_tooling::transaction::{args, Transaction};
 use tari_template_test_tooling::byte_type::ToByteType;
+use tari_template_test_tooling::engine_types::commit_result::ExecuteResult;
+use tari_template_test_tooling::engine_types::events::Event;
+use tari_template_test_tooling::engine_types::virtual_substate::{VirtualSubstate, VirtualSubstateId};
 
 const TEMPLATE_MODULE_NAME: &str = "{{project-name | upper_camel_case}}";
+const EXCHANGE_LIMIT_PERIOD: u64 = 10;
 
 #[test]
 fn it_increases_and_decreases_supply() {
+    let mut setup = setup();
+    // supply changes need two approvals by default
+    let (treasurer_account, treasurer_proof, treasurer_key) =
+        create_admin(&mut setup, [AdminRole::Minter, AdminRole::Burner]);
     let TestSetup {
         mut test,
         stable_coin_component,
@@ -18,45 +31,976 @@ fn it_increases_and_decreases_supply() {
         admin_account,
         admin_badge_resource,
         ..
+    } = setup;
+
+    let mut change_supply = |change: SupplyChange, proposal_id: u64| {
+        test.execute_expect_success(
+            Transaction::builder_localnet()
+                .create_proof(admin_account, admin_badge_resource)
+                .put_last_instruction_output_on_workspace("proof")
+                .call_method(
+                    stable_coin_component,
+                    "propose_supply_change",
+                    args![Workspace("proof"), change],
+                )
+                .drop_all_proofs_in_workspace()
+                .build_and_seal(&admin_key),
+            vec![admin_proof.clone()],
+        );
+        test.execute_expect_success(
+            Transaction::builder_localnet()
+                .create_proof(treasurer_account, admin_badge_resource)
+                .put_last_instruction_output_on_workspace("proof")
+                .call_method(
+                    stable_coin_component,
+                    "approve_supply_change",
+                    args![Workspace("proof"), proposal_id],
+                )
+                .call_method(
+                    stable_coin_component,
+                    "execute_supply_change",
+                    args![proposal_id],
+                )
+                .call_method(stable_coin_component, "total_supply", args![])
+                .drop_all_proofs_in_workspace()
+                .build_and_seal(&treasurer_key),
+            vec![treasurer_proof.clone()],
+        )
+    };
+
+    let result = change_supply(SupplyChange::Increase(Amount::from(123u64)), 0);
+
+    let total_supply = result.finalize.execution_results[4]
+        .decode::<Amount>()
+        .unwrap();
+
+    assert_eq!(total_supply, 1_000_000_123);
+
+    let result = change_supply(SupplyChange::Decrease(Amount::from(456u64)), 1);
+
+    let total_supply = result.finalize.execution_results[4]
+        .decode::<Amount>()
+        .unwrap();
+
+    assert_eq!(total_supply, 1_000_000_123 - 456);
+}
+
+#[test]
+fn it_prevents_unauthorised_users_from_transacting() {
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        ..
+    } = setup();
+
+    let (alice_account, alice_proof, alice_key) = test.create_empty_account();
+    let (bob_account, _, _) = test.create_empty_account();
+
+    // Allow Alice to transact and provision funds in her account
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            // Auth
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            // Withdraw for new stable coin customer
+            .call_method(
+                stable_coin_component,
+                "create_new_user",
+                args![123, alice_account, kyc_data()],
+            )
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(stable_coin_component, "withdraw", args![1234])
+            .put_last_instruction_output_on_workspace("funds")
+            // Deposit badge and funds into Alice's account
+            .call_method(alice_account, "deposit", args![Workspace("badge")])
+            .call_method(alice_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof.clone()],
+    );
+
+    // Alice to Bob should fail (Bob is not allowed to transact)
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 456])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(bob_account, "deposit", args![Workspace("funds")])
+            .call_method(bob_account, "balance", args![token_resource])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof.clone()],
+    );
+
+    assert_reject_reason(reason, "This account does not have permission to deposit");
+}
+
+#[test]
+fn it_allows_users_to_transact() {
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        ..
     } = setup();
 
-    let result = test.execute_expect_success(
+    let (alice_account, alice_proof, alice_key) = test.create_empty_account();
+    let (bob_account, _, _) = test.create_empty_account();
+
+    // Allow Alice to transact and provision funds in her account
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            // Auth
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            // Give alice and bob authorisation to transact using the stable coin resource
+            .call_method(
+                stable_coin_component,
+                "create_new_user",
+                args![1, alice_account, kyc_data()],
+            )
+            .put_last_instruction_output_on_workspace("alice_badge")
+            .call_method(
+                stable_coin_component,
+                "create_new_user",
+                args![2, bob_account, kyc_data()],
+            )
+            .put_last_instruction_output_on_workspace("bob_badge")
+            .call_method(alice_account, "deposit", args![Workspace("alice_badge")])
+            .call_method(bob_account, "deposit", args![Workspace("bob_badge")])
+            // Withdraw for new stable coin customer
+            .call_method(stable_coin_component, "withdraw", args![1234])
+            .put_last_instruction_output_on_workspace("funds")
+            // Deposit badge and funds into Alice's account
+            .call_method(alice_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof.clone()],
+    );
+
+    // Alice to Bob transact again
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 456])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(bob_account, "deposit", args![Workspace("funds")])
+            .call_method(bob_account, "balance", args![token_resource])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof.clone()],
+    );
+
+    let bob_balance = result.finalize.execution_results[5]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(bob_balance, 456);
+}
+
+#[test]
+fn it_allows_admins_to_set_the_exchange_fee() {
+    let mut setup = setup();
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 2000);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        wrapped_token_resource,
+    } = setup;
+
+    let fee = ExchangeFee::Tiered(vec![
+        FeeTier {
+            threshold: Amount::from(0u64),
+            basis_points: 250,
+        },
+        FeeTier {
+            threshold: Amount::from(500u64),
+            basis_points: 100,
+        },
+    ]);
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "set_exchange_fee", args![fee])
+            .call_method(stable_coin_component, "get_exchange_fee", args![])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof],
+    );
+    assert_eq!(
+        result.finalize.execution_results[3]
+            .decode::<ExchangeFee>()
+            .unwrap(),
+        fee
+    );
+    let event = find_event(&result, "set_exchange_fee");
+    assert_eq!(event.get_payload("old_fee").unwrap(), "fixed(5)");
+    assert_eq!(event.get_payload("new_fee").unwrap(), "tiered(0: 250, 500: 100)");
+
+    // 2.5% of 400, then 2.5% of the first 500 and 1% of the remaining 100 of 600, rounded up from 13.5
+    for (amount, expected_balance) in [(400, 390), (600, 976)] {
+        let result = test.execute_expect_success(
+            Transaction::builder_localnet()
+                .create_proof(alice_account, user_badge_resource)
+                .put_last_instruction_output_on_workspace("proof")
+                .call_method(alice_account, "withdraw", args![token_resource, amount])
+                .put_last_instruction_output_on_workspace("funds")
+                .call_method(
+                    stable_coin_component,
+                    "exchange_stable_for_wrapped_tokens",
+                    args![Workspace("proof"), Workspace("funds")],
+                )
+                .put_last_instruction_output_on_workspace("wrapped")
+                .call_method(alice_account, "deposit", args![Workspace("wrapped")])
+                .call_method(alice_account, "balance", args![wrapped_token_resource])
+                .drop_all_proofs_in_workspace()
+                .build_and_seal(&alice_key),
+            vec![alice_proof.clone()],
+        );
+        let balance = result.finalize.execution_results[7]
+            .decode::<Amount>()
+            .unwrap();
+        assert_eq!(balance, expected_balance);
+    }
+
+    // Users cannot change the fee
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "set_exchange_fee",
+                args![ExchangeFee::Fixed(Amount::from(0u64))],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+}
+
+#[test]
+fn it_rejects_invalid_exchange_fees() {
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        ..
+    } = setup();
+
+    let invalid_fees = [
+        (
+            ExchangeFee::BasisPoints(10_001),
+            "Exchange fee cannot be greater than 10000 basis points",
+        ),
+        (
+            ExchangeFee::Capped {
+                basis_points: 100,
+                min: Amount::from(10u64),
+                max: Amount::from(5u64),
+            },
+            "Minimum exchange fee cannot be greater than the maximum",
+        ),
+        (
+            ExchangeFee::Tiered(vec![FeeTier {
+                threshold: Amount::from(100u64),
+                basis_points: 100,
+            }]),
+            "The first fee tier must start at zero",
+        ),
+    ];
+
+    for (fee, expected_reason) in invalid_fees {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .create_proof(admin_account, admin_badge_resource)
+                .put_last_instruction_output_on_workspace("proof")
+                .call_method(stable_coin_component, "set_exchange_fee", args![fee])
+                .drop_all_proofs_in_workspace()
+                .build_and_seal(&admin_key),
+            vec![admin_proof.clone()],
+        );
+        assert_reject_reason(reason, expected_reason);
+    }
+}
+
+#[test]
+fn it_collects_exchange_fees_in_both_directions() {
+    let mut setup = setup();
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 1000);
+    let (treasury_account, _, _) = create_user(&mut setup, 2, 1);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        wrapped_token_resource,
+    } = setup;
+
+    // Exchange 500 stable tokens for wrapped tokens and back, paying the default fee of 5 each time
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 500])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(
+                stable_coin_component,
+                "exchange_stable_for_wrapped_tokens",
+                args![Workspace("proof"), Workspace("funds")],
+            )
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(
+                stable_coin_component,
+                "exchange_wrapped_for_stable_tokens",
+                args![Workspace("proof"), Workspace("wrapped")],
+            )
+            .put_last_instruction_output_on_workspace("stable")
+            .call_method(alice_account, "deposit", args![Workspace("stable")])
+            .call_method(alice_account, "balance", args![token_resource])
+            .call_method(alice_account, "balance", args![wrapped_token_resource])
+            .call_method(stable_coin_component, "collected_fees", args![])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof.clone()],
+    );
+
+    let results = &result.finalize.execution_results;
+    assert_eq!(results[9].decode::<Amount>().unwrap(), 990);
+    assert_eq!(results[10].decode::<Amount>().unwrap(), 0);
+    assert_eq!(results[11].decode::<Amount>().unwrap(), 10);
+    let event = find_event(&result, "exchange_stable_for_wrapped_tokens");
+    assert_eq!(event.get_payload("fee").unwrap(), "5");
+    let event = find_event(&result, "exchange_wrapped_for_stable_tokens");
+    assert_eq!(event.get_payload("amount").unwrap(), "495");
+    assert_eq!(event.get_payload("fee").unwrap(), "5");
+
+    // Users cannot withdraw the fees
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "withdraw_fees", args![10])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(alice_account, "deposit", args![Workspace("fees")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "withdraw_fees", args![10])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(treasury_account, "deposit", args![Workspace("fees")])
+            .call_method(treasury_account, "balance", args![token_resource])
+            .call_method(stable_coin_component, "collected_fees", args![])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof.clone()],
+    );
+
+    let results = &result.finalize.execution_results;
+    assert_eq!(results[5].decode::<Amount>().unwrap(), 11);
+    // The total is kept for reconciliation after the fees are withdrawn
+    assert_eq!(results[6].decode::<Amount>().unwrap(), 10);
+
+    // The fee vault is empty
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "withdraw_fees", args![1])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(treasury_account, "deposit", args![Workspace("fees")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof],
+    );
+    assert_reject_reason(reason, "insufficient funds");
+}
+
+#[test]
+fn it_prevents_blacklisted_users_from_transacting() {
+    let mut setup = setup();
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 1000);
+    let (bob_account, bob_proof, bob_key) = create_user(&mut setup, 2, 1000);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        wrapped_token_resource,
+    } = setup;
+
+    // Alice gets some wrapped tokens before she is blacklisted
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 100])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(
+                stable_coin_component,
+                "exchange_stable_for_wrapped_tokens",
+                args![Workspace("proof"), Workspace("funds")],
+            )
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(alice_account, "deposit", args![Workspace("wrapped")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof.clone()],
+    );
+
+    // Alice's account also holds the badge of another user, which is not recalled when she is blacklisted
+    let alice_badge_vault = test
+        .read_only_state_store()
+        .get_account(alice_account)
+        .unwrap()
+        .get_vault_by_resource(&user_badge_resource)
+        .unwrap()
+        .vault_id();
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "create_new_user",
+                args![3, alice_account, kyc_data()],
+            )
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(alice_account, "deposit", args![Workspace("badge")])
+            .call_method(
+                stable_coin_component,
+                "blacklist_user",
+                args![alice_badge_vault, 1],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof.clone()],
+    );
+
+    let alice_transactions = [
+        // Withdraw stable tokens
+        Transaction::builder_localnet()
+            .call_method(
+                alice_account,
+                "create_proof_by_non_fungible_ids",
+                args![user_badge_resource, vec![NonFungibleId::from_u64(3)]],
+            )
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 10])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(bob_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        // Exchange wrapped tokens for stable tokens
+        Transaction::builder_localnet()
+            .call_method(
+                alice_account,
+                "create_proof_by_non_fungible_ids",
+                args![user_badge_resource, vec![NonFungibleId::from_u64(3)]],
+            )
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![wrapped_token_resource, 10])
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(
+                stable_coin_component,
+                "exchange_wrapped_for_stable_tokens",
+                args![Workspace("proof"), Workspace("wrapped")],
+            )
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(bob_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+    ];
+    for transaction in alice_transactions {
+        let reason = test.execute_expect_failure(transaction, vec![alice_proof.clone()]);
+        assert_reject_reason(reason, "This account is blacklisted");
+    }
+
+    // Exchanging with the recalled badge is not possible
+    test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(
+                alice_account,
+                "create_proof_by_non_fungible_ids",
+                args![user_badge_resource, vec![NonFungibleId::from_u64(1)]],
+            )
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![wrapped_token_resource, 10])
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(
+                stable_coin_component,
+                "exchange_wrapped_for_stable_tokens",
+                args![Workspace("proof"), Workspace("wrapped")],
+            )
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(alice_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof.clone()],
+    );
+
+    // Deposit stable tokens
+    let send_to_alice = |bob_key: &RistrettoSecretKey| {
+        Transaction::builder_localnet()
+            .create_proof(bob_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(bob_account, "withdraw", args![token_resource, 10])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(alice_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(bob_key)
+    };
+    let reason = test.execute_expect_failure(send_to_alice(&bob_key), vec![bob_proof.clone()]);
+    assert_reject_reason(reason, "This account is blacklisted");
+
+    // Alice can transact again once she is removed from the blacklist
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "remove_from_blacklist", args![1])
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(alice_account, "deposit", args![Workspace("badge")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key),
+        vec![admin_proof],
+    );
+    test.execute_expect_success(send_to_alice(&bob_key), vec![bob_proof]);
+}
+
+#[test]
+fn it_resets_exchange_limits_every_period() {
+    let mut setup = setup();
+    setup.test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(setup.admin_account, setup.admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                setup.stable_coin_component,
+                "set_default_exchange_limit",
+                args![500],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&setup.admin_key),
+        vec![setup.admin_proof.clone()],
+    );
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 1000);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        user_badge_resource,
+        token_resource,
+        ..
+    } = setup;
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(3));
+    let exchange = |amount: u64| {
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, amount])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(
+                stable_coin_component,
+                "exchange_stable_for_wrapped_tokens",
+                args![Workspace("proof"), Workspace("funds")],
+            )
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(alice_account, "deposit", args![Workspace("wrapped")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key)
+    };
+    let get_limits = |test: &mut TemplateTest| {
+        let result = test.execute_expect_success(
+            Transaction::builder_localnet()
+                .create_proof(admin_account, admin_badge_resource)
+                .put_last_instruction_output_on_workspace("proof")
+                .call_method(stable_coin_component, "get_user_limits", args![1])
+                .drop_all_proofs_in_workspace()
+                .build_and_seal(&admin_key),
+            vec![admin_proof.clone()],
+        );
+        result.finalize.execution_results[2]
+            .decode::<UserLimits>()
+            .unwrap()
+    };
+
+    test.execute_expect_success(exchange(300), vec![alice_proof.clone()]);
+    let limits = get_limits(&mut test);
+    assert_eq!(
+        limits,
+        UserLimits {
+            wrapped_exchange_limit: Amount::from(500u64),
+            remaining_exchange_limit: Amount::from(200u64),
+            reset_epoch: EXCHANGE_LIMIT_PERIOD,
+        }
+    );
+
+    let reason = test.execute_expect_failure(exchange(300), vec![alice_proof.clone()]);
+    assert_reject_reason(reason, "Exchange limit exceeded");
+
+    // The limit is reset in the next period
+    test.set_virtual_substate(
+        VirtualSubstateId::CurrentEpoch,
+        VirtualSubstate::CurrentEpoch(EXCHANGE_LIMIT_PERIOD + 1),
+    );
+    test.execute_expect_success(exchange(300), vec![alice_proof.clone()]);
+    let limits = get_limits(&mut test);
+    assert_eq!(limits.remaining_exchange_limit, 200);
+    assert_eq!(limits.reset_epoch, 2 * EXCHANGE_LIMIT_PERIOD);
+
+    // Users cannot read the limits of other users
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "get_user_limits", args![2])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        vec![alice_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+}
+
+#[test]
+fn it_restricts_admin_methods_to_their_roles() {
+    let mut setup = setup();
+    let (minter_account, minter_proof, minter_key) =
+        create_admin(&mut setup, [AdminRole::Minter]);
+    // keeps enough minters to approve supply changes once the first one is revoked
+    create_admin(&mut setup, [AdminRole::Minter]);
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        ..
+    } = setup;
+
+    let call = |account: ComponentAddress, key: &RistrettoSecretKey, method: &str| {
+        let args = match method {
+            "create_new_user" => args![1, account, kyc_data()],
+            "create_new_admin" => args!["employee", BTreeSet::from([AdminRole::Minter])],
+            _ => args![100],
+        };
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, method, args)
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(account, "deposit", args![Workspace("badge")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let call_without_output = |account: ComponentAddress, key: &RistrettoSecretKey, method: &str| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, method, args![100])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let propose = |account: ComponentAddress, key: &RistrettoSecretKey, change: SupplyChange| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "propose_supply_change",
+                args![Workspace("proof"), change],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let increase = SupplyChange::Increase(Amount::from(100u64));
+
+    // Each admin can only call the methods of their roles
+    test.execute_expect_success(
+        propose(minter_account, &minter_key, increase.clone()),
+        vec![minter_proof.clone()],
+    );
+    test.execute_expect_success(
+        call(compliance_account, &compliance_key, "create_new_user"),
+        vec![compliance_proof.clone()],
+    );
+    for method in ["set_default_exchange_limit", "withdraw_fees"] {
+        let reason = test.execute_expect_failure(
+            call_without_output(minter_account, &minter_key, method),
+            vec![minter_proof.clone()],
+        );
+        assert_reject_reason(reason, "Access Denied");
+    }
+    let reason = test.execute_expect_failure(
+        propose(
+            minter_account,
+            &minter_key,
+            SupplyChange::Decrease(Amount::from(100u64)),
+        ),
+        vec![minter_proof.clone()],
+    );
+    assert_reject_reason(reason, "Admin does not have the burner role");
+    let reason = test.execute_expect_failure(
+        propose(compliance_account, &compliance_key, increase.clone()),
+        vec![compliance_proof.clone()],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    // Only super-admins can manage admins
+    let reason = test.execute_expect_failure(
+        call(minter_account, &minter_key, "create_new_admin"),
+        vec![minter_proof.clone()],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let (minter_vault, minter_id) = admin_badge(&test, minter_account, admin_badge_resource);
+    let revoke = |vault_id, admin_id| {
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "revoke_admin",
+                args![vault_id, admin_id],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key)
+    };
+    let result = test.execute_expect_success(
+        revoke(minter_vault, minter_id.clone()),
+        vec![admin_proof.clone()],
+    );
+    let event = find_event(&result, "revoke_admin");
+    assert_eq!(event.get_payload("admin_id").unwrap(), minter_id.to_string());
+    assert_eq!(event.get_payload("roles").unwrap(), "minter");
+
+    // The revoked admin has no badge left
+    test.execute_expect_failure(
+        propose(minter_account, &minter_key, increase),
+        vec![minter_proof],
+    );
+
+    let admin_vault = test
+        .read_only_state_store()
+        .get_account(admin_account)
+        .unwrap()
+        .get_vault_by_resource(&admin_badge_resource)
+        .unwrap()
+        .vault_id();
+    let reason = test.execute_expect_failure(
+        revoke(admin_vault, NonFungibleId::from_u64(0)),
+        vec![admin_proof],
+    );
+    assert_reject_reason(reason, "Cannot revoke the last super-admin");
+}
+
+#[test]
+fn it_requires_multiple_approvals_for_supply_changes() {
+    let mut setup = setup();
+    let minters = [
+        create_admin(&mut setup, [AdminRole::Minter, AdminRole::Burner]),
+        create_admin(&mut setup, [AdminRole::Minter, AdminRole::Burner]),
+    ];
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_proof,
+        admin_key,
+        admin_account,
+        admin_badge_resource,
+        ..
+    } = setup;
+
+    let set_policy = |threshold: usize, delay: u64| {
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "set_supply_approval_policy",
+                args![threshold, delay],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&admin_key)
+    };
+
+    // The threshold cannot exceed the number of admins that can approve supply changes
+    let reason = test.execute_expect_failure(set_policy(4, 5), vec![admin_proof.clone()]);
+    assert_reject_reason(
+        reason,
+        "Supply approval threshold of 4 is greater than the 3 admins with the minter role",
+    );
+
+    // Two approvals, and 5 epochs between proposing and executing
+    test.execute_expect_success(set_policy(2, 5), vec![admin_proof.clone()]);
+
+    let (minter_account, minter_proof, minter_key) = &minters[0];
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(*minter_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "propose_supply_change",
+                args![
+                    Workspace("proof"),
+                    SupplyChange::Increase(Amount::from(1000u64))
+                ],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(minter_key),
+        vec![minter_proof.clone()],
+    );
+    let proposal_id = result.finalize.execution_results[2]
+        .decode::<u64>()
+        .unwrap();
+
+    let approve = |account: ComponentAddress, key: &RistrettoSecretKey| {
         Transaction::builder_localnet()
-            .create_proof(admin_account, admin_badge_resource)
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(
+                stable_coin_component,
+                "approve_supply_change",
+                args![Workspace("proof"), proposal_id],
+            )
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let execute = |account: ComponentAddress, key: &RistrettoSecretKey| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            .call_method(stable_coin_component, "increase_supply", args![123])
+            .call_method(
+                stable_coin_component,
+                "execute_supply_change",
+                args![proposal_id],
+            )
             .call_method(stable_coin_component, "total_supply", args![])
             .drop_all_proofs_in_workspace()
-            .build_and_seal(&admin_key),
-        vec![admin_proof.clone()],
+            .build_and_seal(key)
+    };
+
+    let reason = test.execute_expect_failure(
+        execute(*minter_account, minter_key),
+        vec![minter_proof.clone()],
+    );
+    assert_reject_reason(reason, "Supply change has 1 of the 2 required approvals");
+
+    // Approvals must come from distinct admins with the minter role
+    let reason = test.execute_expect_failure(
+        approve(*minter_account, minter_key),
+        vec![minter_proof.clone()],
+    );
+    assert_reject_reason(reason, "Admin has already approved the supply change");
+    let reason = test.execute_expect_failure(
+        approve(compliance_account, &compliance_key),
+        vec![compliance_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let (other_minter_account, other_minter_proof, other_minter_key) = &minters[1];
+    test.execute_expect_success(
+        approve(*other_minter_account, other_minter_key),
+        vec![other_minter_proof.clone()],
+    );
+
+    let reason = test.execute_expect_failure(
+        execute(*minter_account, minter_key),
+        vec![minter_proof.clone()],
     );
+    assert_reject_reason(reason, "Supply change cannot be executed before epoch 5");
 
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(5));
+    let result = test.execute_expect_success(
+        execute(*minter_account, minter_key),
+        vec![minter_proof.clone()],
+    );
     let total_supply = result.finalize.execution_results[3]
         .decode::<Amount>()
         .unwrap();
+    assert_eq!(total_supply, 1_000_001_000);
 
-    assert_eq!(total_supply, 1_000_000_123);
+    // Proposals can only be executed once
+    let reason = test.execute_expect_failure(
+        execute(*minter_account, minter_key),
+        vec![minter_proof.clone()],
+    );
+    assert_reject_reason(reason, "Supply proposal not found");
 
-    let result = test.execute_expect_success(
+    // Admins cannot be revoked if too few would be left to reach the threshold
+    test.execute_expect_success(set_policy(3, 5), vec![admin_proof.clone()]);
+    let (minter_vault, minter_id) = admin_badge(&test, *minter_account, admin_badge_resource);
+    let reason = test.execute_expect_failure(
         Transaction::builder_localnet()
             .create_proof(admin_account, admin_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            .call_method(stable_coin_component, "decrease_supply", args![456])
-            .call_method(stable_coin_component, "total_supply", args![])
+            .call_method(
+                stable_coin_component,
+                "revoke_admin",
+                args![minter_vault, minter_id],
+            )
             .drop_all_proofs_in_workspace()
             .build_and_seal(&admin_key),
         vec![admin_proof],
     );
-
-    let total_supply = result.finalize.execution_results[3]
-        .decode::<Amount>()
-        .unwrap();
-
-    assert_eq!(total_supply, 1_000_000_123 - 456);
+    assert_reject_reason(reason, "Cannot revoke an admin needed to reach the 3 required minter approvals");
 }
 
 #[test]
-fn it_prevents_unauthorised_users_from_transacting() {
+fn it_halts_activity_while_paused() {
+    let mut setup = setup();
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 1000);
+    let (bob_account, _, _) = create_user(&mut setup, 2, 1000);
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
     let TestSetup {
         mut test,
         stable_coin_component,
@@ -67,117 +1011,616 @@ fn it_prevents_unauthorised_users_from_transacting() {
         user_badge_resource,
         token_resource,
         ..
-    } = setup();
+    } = setup;
 
-    let (alice_account, alice_proof, alice_key) = test.create_empty_account();
-    let (bob_account, _, _) = test.create_empty_account();
+    let admin_call = |account: ComponentAddress, key: &RistrettoSecretKey, method: &str| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, method, args![])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let is_paused = |test: &mut TemplateTest| {
+        let result = test.execute_expect_success(
+            Transaction::builder_localnet()
+                .call_method(stable_coin_component, "is_paused", args![])
+                .build_and_seal(&alice_key),
+            vec![],
+        );
+        result.finalize.execution_results[0].decode::<bool>().unwrap()
+    };
 
-    // Allow Alice to transact and provision funds in her account
+    // Compliance officers can pause, but only super-admins can unpause
     test.execute_expect_success(
+        admin_call(compliance_account, &compliance_key, "pause"),
+        vec![compliance_proof.clone()],
+    );
+    assert!(is_paused(&mut test));
+    let reason = test.execute_expect_failure(
+        admin_call(compliance_account, &compliance_key, "unpause"),
+        vec![compliance_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let alice_transactions = [
+        // Transfer stable tokens
         Transaction::builder_localnet()
-            // Auth
-            .create_proof(admin_account, admin_badge_resource)
+            .create_proof(alice_account, user_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            // Withdraw for new stable coin customer
+            .call_method(alice_account, "withdraw", args![token_resource, 10])
+            .put_last_instruction_output_on_workspace("funds")
+            .call_method(bob_account, "deposit", args![Workspace("funds")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+        // Exchange stable tokens for wrapped tokens
+        Transaction::builder_localnet()
+            .create_proof(alice_account, user_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(alice_account, "withdraw", args![token_resource, 10])
+            .put_last_instruction_output_on_workspace("funds")
             .call_method(
                 stable_coin_component,
-                "create_new_user",
-                args![123, alice_account],
+                "exchange_stable_for_wrapped_tokens",
+                args![Workspace("proof"), Workspace("funds")],
             )
-            .put_last_instruction_output_on_workspace("badge")
-            .call_method(stable_coin_component, "withdraw", args![1234])
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(alice_account, "deposit", args![Workspace("wrapped")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key),
+    ];
+    for transaction in alice_transactions.clone() {
+        let reason = test.execute_expect_failure(transaction, vec![alice_proof.clone()]);
+        assert_reject_reason(reason, "Stable coin is paused");
+    }
+
+    // Nothing is deposited into user accounts while paused
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .create_proof(admin_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, "withdraw", args![10])
             .put_last_instruction_output_on_workspace("funds")
-            // Deposit badge and funds into Alice's account
-            .call_method(alice_account, "deposit", args![Workspace("badge")])
             .call_method(alice_account, "deposit", args![Workspace("funds")])
             .drop_all_proofs_in_workspace()
             .build_and_seal(&admin_key),
         vec![admin_proof.clone()],
     );
+    assert_reject_reason(reason, "Stable coin is paused");
+
+    test.execute_expect_success(
+        admin_call(admin_account, &admin_key, "unpause"),
+        vec![admin_proof.clone()],
+    );
+    assert!(!is_paused(&mut test));
+    for transaction in alice_transactions {
+        test.execute_expect_success(transaction, vec![alice_proof.clone()]);
+    }
 
-    // Alice to Bob should fail (Bob is not allowed to transact)
     let reason = test.execute_expect_failure(
+        admin_call(admin_account, &admin_key, "unpause"),
+        vec![admin_proof],
+    );
+    assert_reject_reason(reason, "Stable coin is not paused");
+}
+
+#[test]
+fn it_publishes_reserve_attestations() {
+    let mut setup = setup();
+    let (auditor_account, auditor_proof, auditor_key) =
+        create_admin(&mut setup, [AdminRole::Auditor]);
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_badge_resource,
+        ..
+    } = setup;
+
+    let publish = |account: ComponentAddress,
+                   key: &RistrettoSecretKey,
+                   reserve_amount: u64,
+                   report_hash: Hash32| {
         Transaction::builder_localnet()
-            .create_proof(alice_account, user_badge_resource)
+            .create_proof(account, admin_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            .call_method(alice_account, "withdraw", args![token_resource, 456])
-            .put_last_instruction_output_on_workspace("funds")
-            .call_method(bob_account, "deposit", args![Workspace("funds")])
-            .call_method(bob_account, "balance", args![token_resource])
+            .call_method(
+                stable_coin_component,
+                "publish_reserve_attestation",
+                args![Workspace("proof"), reserve_amount, "USD", report_hash],
+            )
             .drop_all_proofs_in_workspace()
-            .build_and_seal(&alice_key),
-        vec![alice_proof.clone()],
+            .build_and_seal(key)
+    };
+    let views = || {
+        Transaction::builder_localnet()
+            .call_method(stable_coin_component, "latest_attestation", args![])
+            .call_method(stable_coin_component, "attestation_history", args![])
+            .call_method(stable_coin_component, "collateralization_ratio", args![])
+            .build_and_seal(&compliance_key)
+    };
+
+    let reason = test.execute_expect_failure(views(), vec![]);
+    assert_reject_reason(reason, "No reserve attestation has been published");
+
+    // Only auditors can publish attestations
+    let reason = test.execute_expect_failure(
+        publish(compliance_account, &compliance_key, 1000, Hash32::zero()),
+        vec![compliance_proof],
     );
+    assert_reject_reason(reason, "Access Denied");
 
-    assert_reject_reason(reason, "This account does not have permission to deposit");
+    // Attestations must reference an audit report
+    let reason = test.execute_expect_failure(
+        publish(auditor_account, &auditor_key, 1000, Hash32::zero()),
+        vec![auditor_proof.clone()],
+    );
+    assert_reject_reason(reason, "The hash of the audit report is required");
+
+    let result = test.execute_expect_success(
+        publish(auditor_account, &auditor_key, 900_000_000, Hash32::from_array([1; 32])),
+        vec![auditor_proof.clone()],
+    );
+    let auditor_id = find_event(&result, "publish_reserve_attestation")
+        .get_payload("auditor")
+        .unwrap()
+        .to_string();
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(3));
+    test.execute_expect_success(
+        publish(auditor_account, &auditor_key, 1_100_000_000, Hash32::from_array([2; 32])),
+        vec![auditor_proof],
+    );
+
+    let result = test.execute_expect_success(views(), vec![]);
+    let latest = result.finalize.execution_results[0]
+        .decode::<Option<ReserveAttestation>>()
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.reserve_amount, 1_100_000_000);
+    assert_eq!(latest.currency, "USD");
+    assert_eq!(latest.report_hash, Hash32::from_array([2; 32]));
+    assert_eq!(latest.epoch, 3);
+    assert_eq!(latest.auditor.to_string(), auditor_id);
+    // the attestation is signed by the auditor that published it
+    assert_eq!(latest.signer, RistrettoPublicKey::from_secret_key(&auditor_key).to_byte_type());
+
+    let history = result.finalize.execution_results[1]
+        .decode::<Vec<ReserveAttestation>>()
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].reserve_amount, 900_000_000);
+    assert_eq!(history[0].epoch, 0);
+    assert_eq!(history[1], latest);
+
+    // 1.1 billion in reserves for a supply of 1 billion tokens
+    let ratio = result.finalize.execution_results[2]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(ratio, 11_000);
 }
 
 #[test]
-fn it_allows_users_to_transact() {
+fn it_refuses_users_with_expired_kyc() {
+    let mut setup = setup();
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
+    setup
+        .test
+        .set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(5));
+    let (alice_account, alice_proof, alice_key) = create_user(&mut setup, 1, 1000);
     let TestSetup {
         mut test,
         stable_coin_component,
-        admin_proof,
-        admin_key,
-        admin_account,
         admin_badge_resource,
         user_badge_resource,
         token_resource,
         ..
-    } = setup();
+    } = setup;
 
-    let (alice_account, alice_proof, alice_key) = test.create_empty_account();
+    let compliance_call = |method: &str, args| {
+        Transaction::builder_localnet()
+            .create_proof(compliance_account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, method, args)
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&compliance_key)
+    };
+
+    let result = test.execute_expect_success(
+        compliance_call("get_user_data", args![1]),
+        vec![compliance_proof.clone()],
+    );
+    let created_at = result.finalize.execution_results[2]
+        .decode::<UserData>()
+        .unwrap()
+        .created_at;
+    assert_eq!(created_at, 5);
+
+    // KYC details are validated
     let (bob_account, _, _) = test.create_empty_account();
+    let invalid_kyc = [
+        (
+            KycData {
+                jurisdiction: "GBR".to_string(),
+                ..kyc_data()
+            },
+            "Jurisdiction must be a two-letter country code",
+        ),
+        (
+            KycData {
+                expiry_epoch: 5,
+                ..kyc_data()
+            },
+            "KYC expiry epoch must be in the future",
+        ),
+    ];
+    for (kyc, message) in invalid_kyc {
+        let reason = test.execute_expect_failure(
+            compliance_call("create_new_user", args![2, bob_account, kyc]),
+            vec![compliance_proof.clone()],
+        );
+        assert_reject_reason(reason, message);
+    }
 
-    // Allow Alice to transact and provision funds in her account
-    test.execute_expect_success(
+    let exchange = || {
         Transaction::builder_localnet()
-            // Auth
-            .create_proof(admin_account, admin_badge_resource)
+            .create_proof(alice_account, user_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            // Give alice and bob authorisation to transact using the stable coin resource
+            .call_method(alice_account, "withdraw", args![token_resource, 100])
+            .put_last_instruction_output_on_workspace("funds")
             .call_method(
                 stable_coin_component,
-                "create_new_user",
-                args![1, alice_account],
+                "exchange_stable_for_wrapped_tokens",
+                args![Workspace("proof"), Workspace("funds")],
             )
-            .put_last_instruction_output_on_workspace("alice_badge")
+            .put_last_instruction_output_on_workspace("wrapped")
+            .call_method(alice_account, "deposit", args![Workspace("wrapped")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&alice_key)
+    };
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(1000));
+    let reason = test.execute_expect_failure(exchange(), vec![alice_proof.clone()]);
+    assert_reject_reason(reason, "User KYC has expired");
+
+    // Alice can exchange again once her KYC is renewed
+    let renewed_kyc = KycData {
+        tier: 2,
+        jurisdiction: "DE".to_string(),
+        expiry_epoch: 2000,
+    };
+    test.execute_expect_success(
+        compliance_call("set_user_kyc", args![1, renewed_kyc.clone()]),
+        vec![compliance_proof.clone()],
+    );
+    let result = test.execute_expect_success(
+        compliance_call("get_user_kyc", args![1]),
+        vec![compliance_proof],
+    );
+    let kyc = result.finalize.execution_results[2]
+        .decode::<KycData>()
+        .unwrap();
+    assert_eq!(kyc, renewed_kyc);
+    test.execute_expect_success(exchange(), vec![alice_proof]);
+}
+
+#[test]
+fn it_onboards_and_manages_users_in_bulk() {
+    let mut setup = setup();
+    let (compliance_account, compliance_proof, compliance_key) =
+        create_admin(&mut setup, [AdminRole::ComplianceOfficer]);
+    let (limit_manager_account, limit_manager_proof, limit_manager_key) =
+        create_admin(&mut setup, [AdminRole::LimitManager]);
+    let TestSetup {
+        mut test,
+        stable_coin_component,
+        admin_badge_resource,
+        user_badge_resource,
+        ..
+    } = setup;
+    let (alice_account, _, _) = test.create_empty_account();
+    let (bob_account, _, _) = test.create_empty_account();
+
+    let admin_call = |account: ComponentAddress, key: &RistrettoSecretKey, method: &str, args| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(stable_coin_component, method, args)
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(key)
+    };
+    let count_events = |result: &ExecuteResult, topic: &str| {
+        let topic = format!("{TEMPLATE_MODULE_NAME}.{topic}");
+        result
+            .finalize
+            .events
+            .iter()
+            .filter(|e| e.topic() == topic)
+            .count()
+    };
+
+    // The badges are deposited straight into the accounts of the users
+    let users = vec![
+        (1u64, alice_account, Amount::from(500u64), kyc_data()),
+        (2u64, bob_account, Amount::from(700u64), kyc_data()),
+    ];
+    let result = test.execute_expect_success(
+        admin_call(compliance_account, &compliance_key, "create_new_users", args![users]),
+        vec![compliance_proof.clone()],
+    );
+    assert_eq!(count_events(&result, "create_new_user"), 2);
+
+    // Once the accounts have received their badges, the deposit rule is the one set at creation
+    let user_badges = test
+        .read_only_state_store()
+        .get_resource(&user_badge_resource)
+        .unwrap();
+    assert_eq!(
+        *user_badges
+            .access_rules()
+            .get_access_rule(&ResourceAuthAction::Deposit),
+        rule!(any_of(
+            resource(admin_badge_resource),
+            component(stable_coin_component)
+        ))
+    );
+
+    let badge_vaults = [alice_account, bob_account].map(|account| {
+        test.read_only_state_store()
+            .get_account(account)
+            .unwrap()
+            .get_vault_by_resource(&user_badge_resource)
+            .unwrap()
+            .vault_id()
+    });
+    let badge_ids = |test: &mut TemplateTest, vault_id| {
+        test.read_only_state_store()
+            .get_vault(&vault_id)
+            .unwrap()
+            .get_non_fungible_ids()
+            .clone()
+    };
+    assert_eq!(
+        badge_ids(&mut test, badge_vaults[0]),
+        BTreeSet::from([NonFungibleId::from_u64(1)])
+    );
+    assert_eq!(
+        badge_ids(&mut test, badge_vaults[1]),
+        BTreeSet::from([NonFungibleId::from_u64(2)])
+    );
+
+    let get_limit = |test: &mut TemplateTest, user_id: u64| {
+        let result = test.execute_expect_success(
+            admin_call(compliance_account, &compliance_key, "get_user_limits", args![user_id]),
+            vec![compliance_proof.clone()],
+        );
+        result.finalize.execution_results[2]
+            .decode::<UserLimits>()
+            .unwrap()
+            .wrapped_exchange_limit
+    };
+    assert_eq!(get_limit(&mut test, 1), 500);
+    assert_eq!(get_limit(&mut test, 2), 700);
+
+    let limits = vec![(1u64, Amount::from(800u64)), (2u64, Amount::from(900u64))];
+    let result = test.execute_expect_success(
+        admin_call(
+            limit_manager_account,
+            &limit_manager_key,
+            "set_user_exchange_limits",
+            args![limits],
+        ),
+        vec![limit_manager_proof],
+    );
+    assert_eq!(count_events(&result, "set_user_exchange_limit"), 2);
+    assert_eq!(get_limit(&mut test, 1), 800);
+    assert_eq!(get_limit(&mut test, 2), 900);
+
+    let blacklist = vec![(badge_vaults[0], 1u64), (badge_vaults[1], 2u64)];
+    let result = test.execute_expect_success(
+        admin_call(compliance_account, &compliance_key, "blacklist_users", args![blacklist]),
+        vec![compliance_proof],
+    );
+    assert_eq!(count_events(&result, "blacklist_user"), 2);
+    for vault_id in badge_vaults {
+        assert!(badge_ids(&mut test, vault_id).is_empty());
+    }
+}
+
+#[derive(Debug, minicbor::Decode)]
+struct UserData {
+    #[n(0)]
+    _user_id: u64,
+    #[n(1)]
+    _user_account: ComponentAddress,
+    #[n(2)]
+    created_at: u64,
+}
+
+// Mirrors the supply changes of the template
+#[derive(Debug, Clone, minicbor::Encode, minicbor::CborLen)]
+enum SupplyChange {
+    #[n(0)]
+    Increase(#[n(0)] Amount),
+    #[n(1)]
+    Decrease(#[n(0)] Amount),
+}
+
+// Mirrors the admin roles of the template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, minicbor::Encode, minicbor::CborLen)]
+#[cbor(index_only)]
+enum AdminRole {
+    #[n(0)]
+    Minter,
+    #[n(1)]
+    Burner,
+    #[n(2)]
+    ComplianceOfficer,
+    #[n(3)]
+    LimitManager,
+    #[n(5)]
+    Auditor,
+}
+
+// Mirrors the fee types of the template, which cannot be imported from a cdylib crate
+#[derive(Debug, Clone, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+enum ExchangeFee {
+    #[n(0)]
+    Fixed(#[n(0)] Amount),
+    #[n(1)]
+    BasisPoints(#[n(0)] u16),
+    #[n(2)]
+    Capped {
+        #[n(0)]
+        basis_points: u16,
+        #[n(1)]
+        min: Amount,
+        #[n(2)]
+        max: Amount,
+    },
+    #[n(3)]
+    Tiered(#[n(0)] Vec<FeeTier>),
+}
+
+#[derive(Debug, Clone, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+struct FeeTier {
+    #[n(0)]
+    threshold: Amount,
+    #[n(1)]
+    basis_points: u16,
+}
+
+#[derive(Debug, PartialEq, minicbor::Decode)]
+struct ReserveAttestation {
+    #[n(0)]
+    reserve_amount: Amount,
+    #[n(1)]
+    currency: String,
+    #[n(2)]
+    report_hash: Hash32,
+    #[n(3)]
+    epoch: u64,
+    #[n(4)]
+    auditor: NonFungibleId,
+    #[n(5)]
+    signer: RistrettoPublicKeyBytes,
+}
+
+#[derive(Debug, Clone, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+struct KycData {
+    #[n(0)]
+    tier: u8,
+    #[n(1)]
+    jurisdiction: String,
+    #[n(2)]
+    expiry_epoch: u64,
+}
+
+// KYC details that are valid for the duration of the tests
+fn kyc_data() -> KycData {
+    KycData {
+        tier: 1,
+        jurisdiction: "GB".to_string(),
+        expiry_epoch: 1000,
+    }
+}
+
+#[derive(Debug, PartialEq, minicbor::Decode)]
+struct UserLimits {
+    #[n(0)]
+    wrapped_exchange_limit: Amount,
+    #[n(1)]
+    remaining_exchange_limit: Amount,
+    #[n(2)]
+    reset_epoch: u64,
+}
+
+fn find_event<'a>(result: &'a ExecuteResult, topic: &str) -> &'a Event {
+    let topic = format!("{TEMPLATE_MODULE_NAME}.{topic}");
+    result
+        .finalize
+        .events
+        .iter()
+        .find(|e| e.topic() == topic)
+        .unwrap_or_else(|| panic!("{topic} event not found"))
+}
+
+// Creates an account with a user badge and some stable coin tokens
+fn create_user(
+    setup: &mut TestSetup,
+    user_id: u64,
+    funds: u64,
+) -> (ComponentAddress, NonFungibleAddress, RistrettoSecretKey) {
+    let (account, proof, key) = setup.test.create_empty_account();
+    setup.test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(setup.admin_account, setup.admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
             .call_method(
-                stable_coin_component,
+                setup.stable_coin_component,
                 "create_new_user",
-                args![2, bob_account],
+                args![user_id, account, kyc_data()],
             )
-            .put_last_instruction_output_on_workspace("bob_badge")
-            .call_method(alice_account, "deposit", args![Workspace("alice_badge")])
-            .call_method(bob_account, "deposit", args![Workspace("bob_badge")])
-            // Withdraw for new stable coin customer
-            .call_method(stable_coin_component, "withdraw", args![1234])
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(setup.stable_coin_component, "withdraw", args![funds])
             .put_last_instruction_output_on_workspace("funds")
-            // Deposit badge and funds into Alice's account
-            .call_method(alice_account, "deposit", args![Workspace("funds")])
+            .call_method(account, "deposit", args![Workspace("badge")])
+            .call_method(account, "deposit", args![Workspace("funds")])
             .drop_all_proofs_in_workspace()
-            .build_and_seal(&admin_key),
-        vec![admin_proof.clone()],
+            .build_and_seal(&setup.admin_key),
+        vec![setup.admin_proof.clone()],
     );
+    (account, proof, key)
+}
 
-    // Alice to Bob transact again
-    let result = test.execute_expect_success(
+// Creates an account with an admin badge that has the roles
+fn create_admin<const N: usize>(
+    setup: &mut TestSetup,
+    roles: [AdminRole; N],
+) -> (ComponentAddress, NonFungibleAddress, RistrettoSecretKey) {
+    let (account, proof, key) = setup.test.create_empty_account();
+    setup.test.execute_expect_success(
         Transaction::builder_localnet()
-            .create_proof(alice_account, user_badge_resource)
+            .create_proof(setup.admin_account, setup.admin_badge_resource)
             .put_last_instruction_output_on_workspace("proof")
-            .call_method(alice_account, "withdraw", args![token_resource, 456])
-            .put_last_instruction_output_on_workspace("funds")
-            .call_method(bob_account, "deposit", args![Workspace("funds")])
-            .call_method(bob_account, "balance", args![token_resource])
+            .call_method(
+                setup.stable_coin_component,
+                "create_new_admin",
+                args!["employee", BTreeSet::from(roles)],
+            )
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(account, "deposit", args![Workspace("badge")])
             .drop_all_proofs_in_workspace()
-            .build_and_seal(&alice_key),
-        vec![alice_proof.clone()],
+            .build_and_seal(&setup.admin_key),
+        vec![setup.admin_proof.clone()],
     );
+    (account, proof, key)
+}
 
-    let bob_balance = result.finalize.execution_results[5]
-        .decode::<Amount>()
+// The vault and id of the only admin badge in the account
+fn admin_badge(
+    test: &TemplateTest,
+    account: ComponentAddress,
+    admin_badge_resource: ResourceAddress,
+) -> (VaultId, NonFungibleId) {
+    let vault_id = test
+        .read_only_state_store()
+        .get_account(account)
+        .unwrap()
+        .get_vault_by_resource(&admin_badge_resource)
+        .unwrap()
+        .vault_id();
+    let admin_id = test
+        .read_only_state_store()
+        .get_vault(&vault_id)
+        .unwrap()
+        .get_non_fungible_ids()
+        .first()
+        .cloned()
         .unwrap();
-    assert_eq!(bob_balance, 456);
+    (vault_id, admin_id)
 }
 
 struct TestSetup {
@@ -189,6 +1632,7 @@ struct TestSetup {
     admin_badge_resource: ResourceAddress,
     user_badge_resource: ResourceAddress,
     token_resource: ResourceAddress,
+    wrapped_token_resource: ResourceAddress,
 }
 
 fn setup() -> TestSetup {
@@ -209,7 +1653,15 @@ fn setup() -> TestSetup {
             .call_function(
                 template,
                 "instantiate",
-                args![1_000_000_000, "SC4U", metadata, view_key, true],
+                args![
+                    1_000_000_000,
+                    "SC4U",
+                    metadata,
+                    view_key,
+                    true,
+                    1000,
+                    EXCHANGE_LIMIT_PERIOD
+                ],
             )
             .put_last_instruction_output_on_workspace("admin_badge")
             .call_method(admin_account, "deposit", args![Workspace("admin_badge")])
@@ -249,12 +1701,21 @@ fn setup() -> TestSetup {
         .get_value("$.2")
         .unwrap()
         .expect("admin_auth_resource not found");
+    let wrapped_token_vault = indexed
+        .get_value("$.4.0")
+        .unwrap()
+        .expect("wrapped_token not found");
 
     let vault = test
         .read_only_state_store()
         .get_vault(&token_vault)
         .unwrap();
     let token_resource = *vault.resource_address();
+    let wrapped_token_resource = *test
+        .read_only_state_store()
+        .get_vault(&wrapped_token_vault)
+        .unwrap()
+        .resource_address();
 
     TestSetup {
         test,
@@ -265,5 +1726,6 @@ fn setup() -> TestSetup {
         admin_badge_resource,
         user_badge_resource,
         token_resource,
+        wrapped_token_resource,
     }
 }
@@ -1,6 +1,97 @@
+mod stable_swap;
+
 use std::collections::BTreeMap;
 use tari_template_lib::prelude::*;
 
+// Amount of LP tokens permanently locked in the pool on the first deposit
+// so the LP supply can never go back to zero, making the LP token price impossible to manipulate by dust deposits
+const MINIMUM_LIQUIDITY: Amount = amount!(1000);
+
+// Fees are represented as a per-mil quantity (e.g. "1" represents "0.1%")
+const PER_MIL: u64 = 1000;
+
+// Maximum pool fee, as a per-mil (10%), so that the admin cannot set a fee that takes most of each swap
+const MAX_FEE: u16 = 100;
+
+// Maximum share of each swap fee that can be diverted to the protocol, as a per-mil of the fee (50%)
+const MAX_PROTOCOL_FEE_SHARE: u16 = 500;
+
+// Bounds of the amplification coefficient of StableSwap pools
+const MAX_AMPLIFICATION: u64 = 10_000;
+
+// Price impacts are represented as a basis points quantity (e.g. "25" represents "0.25%")
+const BASIS_POINTS: u64 = 10_000;
+
+// Oracle prices are fixed point numbers with 12 decimals (e.g. "1_500_000_000_000" represents a price of 1.5)
+const PRICE_SCALE: u64 = 1_000_000_000_000;
+
+// Maximum number of price observations kept by the oracle, older observations are discarded
+const MAX_OBSERVATIONS: usize = 128;
+
+// Expected result of swapping an amount of tokens, without modifying the pool
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct SwapQuote {
+    #[n(0)]
+    pub output_amount: Amount,
+    // amount of input tokens kept as the swap fee, which goes to the LP holders except for the protocol fee share
+    #[n(1)]
+    pub fee_amount: Amount,
+    // how much worse the swap price is compared to the current pool price, in basis points
+    #[n(2)]
+    pub price_impact: u16,
+}
+
+// Accumulated prices of the pool resources at the start of an epoch
+// the price of each resource is expressed in units of the other resource, and accumulated once per elapsed epoch
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct Observation {
+    #[n(0)]
+    pub epoch: u64,
+    #[n(1)]
+    pub a_price_cumulative: Amount,
+    #[n(2)]
+    pub b_price_cumulative: Amount,
+}
+
+// Invariant used by a pool to price the swaps
+#[derive(Debug, Clone, Copy, PartialEq, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub enum Curve {
+    // "x * y = k", suited for most pairs
+    #[n(0)]
+    ConstantProduct,
+    // StableSwap invariant, with much lower slippage for pairs that trade close to 1:1 (e.g. a stable coin and its wrapped version)
+    // the higher the amplification, the flatter the curve is around the 1:1 price
+    #[n(1)]
+    Stable(#[n(0)] u64),
+}
+
+// Immutable data of the receipt of a flash loan, records what must be paid back to the pool
+#[derive(Debug, Clone, minicbor::Encode, minicbor::Decode, minicbor::CborLen)]
+pub struct FlashLoanReceipt {
+    #[n(0)]
+    pub resource: ResourceAddress,
+    #[n(1)]
+    pub amount: Amount,
+    #[n(2)]
+    pub fee: Amount,
+}
+
+// integer square root, rounded down, using Newton's method
+fn sqrt(value: Amount) -> Amount {
+    let n = value.to_u128();
+    if n < 2 {
+        return value;
+    }
+
+    let mut x = n;
+    let mut y = n / 2 + (n & 1);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    Amount::new(x)
+}
+
 #[template]
 mod asd {
     use super::*;
@@ -9,113 +100,453 @@ mod asd {
     pub struct {{ project-name | upper_camel_case }}Pool {
         pools: BTreeMap<ResourceAddress, Vault>,
         lp_resource: ResourceManager,
+        locked_lp: Vault,
         fee: u16,
+        curve: Curve,
+        admin_badge_resource: ResourceAddress,
+        fee_collector_resource: ResourceManager,
+        fee_collector_badge_count: u64,
+        // per-mil of each swap fee that is kept for the protocol instead of the LP holders, 0 means disabled
+        protocol_fee_share: u16,
+        protocol_fees: BTreeMap<ResourceAddress, Vault>,
+        // price history for the TWAP oracle, sorted by epoch with at most one observation per epoch
+        observations: Vec<Observation>,
+        flash_loan_receipt_resource: ResourceManager,
+        flash_loan_count: u64,
+        // number of flash loans not yet repaid in the current transaction
+        active_flash_loans: u32,
     }
 
     impl {{ project-name | upper_camel_case }}Pool {
         // Initialises a new pool component for for the pool A - B
         // the fees is represented as a per-mil quantity (e.g. "1" represents "0.1%")
-        pub fn new(a_addr: ResourceAddress, b_addr: ResourceAddress, fee: u16) -> Self {
+        // returns the pool component alongside the admin badge, which is needed to manage the fees of the pool
+        pub fn new(a_addr: ResourceAddress, b_addr: ResourceAddress, fee: u16) -> (Component<Self>, Bucket) {
+            Self::create(a_addr, b_addr, fee, Curve::ConstantProduct)
+        }
+
+        // Initialises a new pool component for the pool A - B that uses the StableSwap invariant
+        // intended for resources of the same value, it has the same interface as the constant product pools
+        pub fn new_stable(
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            amplification: u64,
+        ) -> (Component<Self>, Bucket) {
+            assert!(
+                (1..=MAX_AMPLIFICATION).contains(&amplification),
+                "Invalid amplification {}, it must be between 1 and {}",
+                amplification,
+                MAX_AMPLIFICATION
+            );
+            Self::create(a_addr, b_addr, fee, Curve::Stable(amplification))
+        }
+
+        fn create(
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            curve: Curve,
+        ) -> (Component<Self>, Bucket) {
             // check that the the resource pair is correct
             assert_ne!(a_addr, b_addr, "The resources of the pair must be different");
             Self::check_resource_is_fungible(a_addr);
             Self::check_resource_is_fungible(b_addr);
 
-            // the fee represents a percentage, so it must be between 0 and 100
-            let valid_fee_range = 0..1000;
-            assert!(valid_fee_range.contains(&fee), "Invalid fee {}", fee);
+            Self::check_fee(fee);
 
             // create the vaults to store the funds
             let mut pools = BTreeMap::new();
             pools.insert(a_addr, Vault::new_empty(a_addr));
             pools.insert(b_addr, Vault::new_empty(b_addr));
 
-            // create the lp resource
-            // TODO: add lp resource minting/burning security, only this component should be allowed
-            let lp_resource = ResourceBuilder::public_fungible().with_token_symbol("LP").build();
+            // the protocol fees are kept apart from the pools, so they do not count as liquidity
+            let mut protocol_fees = BTreeMap::new();
+            protocol_fees.insert(a_addr, Vault::new_empty(a_addr));
+            protocol_fees.insert(b_addr, Vault::new_empty(b_addr));
 
-            Self {
+            // create the admin badge, that controls the fees of the pool
+            let admin_badge =
+                ResourceBuilder::non_fungible().initial_supply(Some(NonFungibleId::from_u64(0)));
+            let admin_badge_resource = admin_badge.resource_address();
+            let require_admin = rule!(resource(admin_badge_resource));
+
+            // allocate the component address up front, so only this component is allowed to mint and burn LP tokens
+            let component_alloc = CallerContext::allocate_component_address(None);
+            let require_pool = rule!(component(component_alloc.get_address()));
+
+            // create the lp resource
+            // LOCKED makes the rules immutable, and with no owner nobody else can bypass them
+            let lp_resource = ResourceBuilder::public_fungible()
+                .with_token_symbol("LP")
+                .with_owner_rule(OwnerRule::None)
+                .mintable(require_pool.clone(), LOCKED)
+                .burnable(require_pool.clone(), LOCKED)
+                .build();
+
+            // create the fee collector badge resource, new badges are only issued by the pool at the request of the admin
+            let fee_collector_resource = ResourceBuilder::non_fungible()
+                .with_owner_rule(OwnerRule::None)
+                .mintable(require_pool.clone(), LOCKED)
+                .build();
+            let require_fee_collector = rule!(resource(fee_collector_resource));
+
+            // create the flash loan receipt resource
+            // receipts cannot be deposited anywhere, so the only way to get rid of one is repaying the loan
+            let flash_loan_receipt_resource = ResourceBuilder::non_fungible()
+                .with_owner_rule(OwnerRule::None)
+                .mintable(require_pool.clone(), LOCKED)
+                .burnable(require_pool.clone(), LOCKED)
+                .depositable(AccessRule::DenyAll, LOCKED)
+                .build();
+
+            let access_rules = AccessRules::allow_all()
+                .add_method_rule("set_fee", require_admin.clone())
+                .add_method_rule("set_protocol_fee_share", require_admin.clone())
+                .add_method_rule("create_fee_collector_badge", require_admin)
+                .add_method_rule("withdraw_protocol_fees", require_fee_collector);
+
+            let component = Component::new(Self {
                 pools,
                 lp_resource: ResourceManager::get(lp_resource),
+                locked_lp: Vault::new_empty(lp_resource),
                 fee,
-            }
+                curve,
+                admin_badge_resource,
+                fee_collector_resource: ResourceManager::get(fee_collector_resource),
+                fee_collector_badge_count: 0,
+                protocol_fee_share: 0,
+                protocol_fees,
+                observations: vec![Observation {
+                    epoch: Consensus::current_epoch(),
+                    a_price_cumulative: Amount::zero(),
+                    b_price_cumulative: Amount::zero(),
+                }],
+                flash_loan_receipt_resource: ResourceManager::get(flash_loan_receipt_resource),
+                flash_loan_count: 0,
+                active_flash_loans: 0,
+            })
+            .with_address_allocation(component_alloc)
+            .with_access_rules(access_rules)
+            // the pool is managed by anyone with the admin badge, there is no single owner
+            .with_owner_rule(OwnerRule::None)
+            .create();
+
+            (component, admin_badge)
+        }
+
+        // updates the fee of the pool, only callable by the admin
+        pub fn set_fee(&mut self, fee: u16) {
+            Self::check_fee(fee);
+            let old_fee = self.fee;
+            self.fee = fee;
+
+            emit_event("set_fee", [("old_fee", old_fee.to_string()), ("fee", fee.to_string())]);
+        }
+
+        // updates the share of each swap fee that goes to the protocol, only callable by the admin
+        // the share is represented as a per-mil of the fee (e.g. "100" keeps 10% of the fees for the protocol)
+        pub fn set_protocol_fee_share(&mut self, protocol_fee_share: u16) {
+            assert!(
+                protocol_fee_share <= MAX_PROTOCOL_FEE_SHARE,
+                "Invalid protocol fee share {}, it must not be greater than {}",
+                protocol_fee_share,
+                MAX_PROTOCOL_FEE_SHARE
+            );
+            let old_protocol_fee_share = self.protocol_fee_share;
+            self.protocol_fee_share = protocol_fee_share;
+
+            emit_event(
+                "set_protocol_fee_share",
+                [
+                    ("old_protocol_fee_share", old_protocol_fee_share.to_string()),
+                    ("protocol_fee_share", protocol_fee_share.to_string()),
+                ],
+            );
+        }
+
+        // issues a new badge that allows withdrawing the protocol fees, only callable by the admin
+        pub fn create_fee_collector_badge(&mut self) -> Bucket {
+            let id = NonFungibleId::from_u64(self.fee_collector_badge_count);
+            self.fee_collector_badge_count += 1;
+            self.fee_collector_resource.mint_non_fungible(id, &(), &())
+        }
+
+        // withdraws all the protocol fees accrued for a resource, only callable by a fee collector
+        pub fn withdraw_protocol_fees(&mut self, resource: ResourceAddress) -> Bucket {
+            let vault = self
+                .protocol_fees
+                .get_mut(&resource)
+                .unwrap_or_else(|| panic!("Resource {} is not in the pool", resource));
+            let bucket = vault.withdraw_all();
+
+            emit_event(
+                "withdraw_protocol_fees",
+                [("resource", resource.to_string()), ("amount", bucket.amount().to_string())],
+            );
+
+            bucket
         }
 
         // swap A tokens for B tokens or vice versa
+        // WARNING: no slippage protection is applied, use "swap_exact_in" or "swap_exact_out" instead
         pub fn swap(&mut self, input_bucket: Bucket, output_resource: ResourceAddress) -> Bucket {
-            // check that the parameters are correct
             let input_resource = input_bucket.resource_address();
-            self.check_pool_resources(input_resource, output_resource);
+            let output_amount =
+                self.get_amount_out(input_resource, output_resource, input_bucket.amount());
+            self.perform_swap(input_bucket, output_resource, output_amount)
+        }
+
+        // swap all the tokens in the input bucket
+        // the transaction is rejected if the output is lower than "min_output" or the epoch "valid_until_epoch" has passed
+        pub fn swap_exact_in(
+            &mut self,
+            input_bucket: Bucket,
+            output_resource: ResourceAddress,
+            min_output: Amount,
+            valid_until_epoch: u64,
+        ) -> Bucket {
+            Self::check_deadline(valid_until_epoch);
+
+            let input_resource = input_bucket.resource_address();
+            let output_amount =
+                self.get_amount_out(input_resource, output_resource, input_bucket.amount());
+            assert!(
+                output_amount >= min_output,
+                "Insufficient output amount: {} is lower than the minimum {}",
+                output_amount,
+                min_output
+            );
+
+            self.perform_swap(input_bucket, output_resource, output_amount)
+        }
 
-            // get the data needed to calculate the pool rebalancing
+        // swap input tokens for an exact amount of output tokens
+        // the input bucket represents the maximum the user is willing to pay, the unused input tokens are returned
+        // the transaction is rejected if the input bucket is not enough or the epoch "valid_until_epoch" has passed
+        pub fn swap_exact_out(
+            &mut self,
+            mut input_bucket: Bucket,
+            output_resource: ResourceAddress,
+            output_amount: Amount,
+            valid_until_epoch: u64,
+        ) -> (Bucket, Bucket) {
+            Self::check_deadline(valid_until_epoch);
+
+            let input_resource = input_bucket.resource_address();
+            let input_amount = self.get_amount_in(input_resource, output_resource, output_amount);
+            assert!(
+                input_amount <= input_bucket.amount(),
+                "Excessive input amount: {} is required but only {} was provided",
+                input_amount,
+                input_bucket.amount()
+            );
+
+            let input = input_bucket.take(input_amount);
+            let output_bucket = self.perform_swap(input, output_resource, output_amount);
+
+            // return the output tokens alongside the unused input tokens
+            (output_bucket, input_bucket)
+        }
+
+        // calculates the result of swapping "amount" input tokens for the other resource in the pool
+        pub fn quote(&self, input_resource: ResourceAddress, amount: Amount) -> SwapQuote {
+            self.check_no_active_flash_loans();
+            let output_resource = self.get_other_resource(input_resource);
+            let output_amount = self.get_amount_out(input_resource, output_resource, amount);
+            let fee_amount = amount - self.apply_fee(amount);
+
+            // the price impact compares the output against the one obtained at the current pool price
+            // the fee is not considered, as it does not depend on the size of the swap
             let input_pool_balance = self.get_pool_balance(input_resource);
             let output_pool_balance = self.get_pool_balance(output_resource);
+            let spot_output_amount =
+                self.get_spot_output_amount(self.apply_fee(amount), input_pool_balance, output_pool_balance);
+            let price_impact = if spot_output_amount <= output_amount {
+                0
+            } else {
+                let basis_points = Amount::from(BASIS_POINTS);
+                let impact = ((spot_output_amount - output_amount) * basis_points) / spot_output_amount;
+                impact.to_u64_checked().unwrap() as u16
+            };
+
+            SwapQuote {
+                output_amount,
+                fee_amount,
+                price_impact,
+            }
+        }
 
-            // check that the pools are not empty, to prevent division by 0 errors later
+        // lends tokens from the pool, returning them alongside a receipt for the loan
+        // the receipt can only be burned by repaying the loan plus the fee with "repay_flash_loan" in the same transaction,
+        // otherwise the transaction fails. Swaps, liquidity changes and price reads (quotes and the TWAP oracle) are not
+        // allowed until the loan is repaid
+        pub fn flash_loan(&mut self, resource: ResourceAddress, amount: Amount) -> (Bucket, Bucket) {
+            assert!(!amount.is_zero(), "The flash loan amount must be positive");
+            // checkpoint the oracle before the reserves change
+            self.update_oracle();
+            let pool_balance = self.get_pool_balance(resource);
             assert!(
-                !input_pool_balance.is_zero(),
-                "The pool for resource '{}' is empty",
-                input_resource
+                amount <= pool_balance,
+                "Insufficient liquidity in the pool for resource '{}'",
+                resource
             );
-            assert!(
-                !output_pool_balance.is_zero(),
-                "The pool for resource '{}' is empty",
-                output_resource
+
+            // the fee is the same as for swaps, rounded up in favour of the pool
+            let fee = (amount * Amount::from(self.fee)).div_ceil(Amount::from(PER_MIL));
+
+            let id = NonFungibleId::from_u64(self.flash_loan_count);
+            self.flash_loan_count += 1;
+            self.active_flash_loans += 1;
+            let receipt = FlashLoanReceipt { resource, amount, fee };
+            let receipt_bucket = self.flash_loan_receipt_resource.mint_non_fungible(id, &receipt, &());
+
+            let loan_bucket = self.pools.get_mut(&resource).unwrap().withdraw(amount);
+
+            emit_event(
+                "flash_loan",
+                [
+                    ("resource", resource.to_string()),
+                    ("amount", amount.to_string()),
+                    ("fee", fee.to_string()),
+                ],
             );
 
-            // apply the fee to the input bucket
-            // so the user will get a lesser amount of tokens than the theoretical (for the gain of the LP holders)
-            let input_bucket_balance = input_bucket.amount();
-            let effective_input_balance = input_bucket_balance - (input_bucket_balance * Amount::from(self.fee)) / Amount::from(1000u64);
+            (loan_bucket, receipt_bucket)
+        }
 
-            // recalculate the new vault balances for the swap
-            // constant product AMM formula is "k = a * b"
-            // so the new output vault balance should be "b = k / a"
-            let k = input_pool_balance * output_pool_balance;
-            let new_input_pool_balance = input_pool_balance + effective_input_balance;
-            let new_output_pool_balance = k / new_input_pool_balance;
+        // repays a flash loan, burning its receipt and returning any tokens not needed for the repayment
+        pub fn repay_flash_loan(&mut self, receipt_bucket: Bucket, mut repayment_bucket: Bucket) -> Bucket {
+            assert_eq!(
+                receipt_bucket.resource_address(),
+                self.flash_loan_receipt_resource.resource_address(),
+                "Invalid flash loan receipt"
+            );
+            let receipts = receipt_bucket.get_non_fungibles();
+            assert_eq!(receipts.len(), 1, "Flash loans must be repaid one at a time");
+            let receipt = receipts[0].get_data::<FlashLoanReceipt>();
 
-            // calculate the amount of output tokens to return to the user
-            let output_bucket_amount = output_pool_balance - new_output_pool_balance;
+            assert_eq!(
+                repayment_bucket.resource_address(),
+                receipt.resource,
+                "Invalid repayment resource"
+            );
+            let repayment_amount = receipt.amount + receipt.fee;
+            assert!(
+                repayment_bucket.amount() >= repayment_amount,
+                "Insufficient flash loan repayment: {} is required but only {} was provided",
+                repayment_amount,
+                repayment_bucket.amount()
+            );
 
-            // perform the swap
-            self.pools
-                .get_mut(&input_resource)
-                .unwrap()
-                .deposit(input_bucket);
-            self.pools
-                .get_mut(&output_resource)
-                .unwrap()
-                .withdraw(output_bucket_amount)
+            self.update_oracle();
+            let repayment = repayment_bucket.take(repayment_amount);
+            self.pools.get_mut(&receipt.resource).unwrap().deposit(repayment);
+            receipt_bucket.burn();
+            self.active_flash_loans -= 1;
+
+            emit_event(
+                "flash_loan_repaid",
+                [
+                    ("resource", receipt.resource.to_string()),
+                    ("amount", receipt.amount.to_string()),
+                    ("fee", receipt.fee.to_string()),
+                ],
+            );
+            self.emit_sync_event();
+
+            repayment_bucket
         }
 
-        pub fn add_liquidity(&mut self, a_bucket: Bucket, b_bucket: Bucket) -> Bucket {
+        // adds liquidity to the pool, returning the new LP tokens and the unused tokens of the excess side
+        pub fn add_liquidity(&mut self, mut a_bucket: Bucket, mut b_bucket: Bucket) -> (Bucket, Bucket) {
             // check that the buckets are correct
             let a_resource = a_bucket.resource_address();
             let b_resource = b_bucket.resource_address();
             self.check_pool_resources(a_resource, b_resource);
+            self.check_no_active_flash_loans();
 
             // extract the bucket amounts for later
             let a_amount = a_bucket.amount();
             let b_amount = b_bucket.amount();
+            assert!(
+                !a_amount.is_zero() && !b_amount.is_zero(),
+                "Liquidity must be provided for both resources"
+            );
 
-            // add the liquidity to the pool
-            self.pools.get_mut(&a_resource).unwrap().deposit(a_bucket);
-            self.pools.get_mut(&b_resource).unwrap().deposit(b_bucket);
+            let a_balance = self.get_pool_balance(a_resource);
+            let b_balance = self.get_pool_balance(b_resource);
+            let lp_supply = self.lp_total_supply();
+
+            self.update_oracle();
+
+            let (new_lp_amount, a_used, b_used) = if lp_supply.is_zero() {
+                // the first deposit sets the pool price, the LP amount is the geometric mean of the deposit
+                let liquidity = sqrt(a_amount * b_amount);
+                assert!(
+                    liquidity > MINIMUM_LIQUIDITY,
+                    "Insufficient initial liquidity, it must be greater than {}",
+                    MINIMUM_LIQUIDITY
+                );
+
+                // lock the minimum liquidity forever, it will never be withdrawn from the pool
+                let locked_lp = self.lp_resource.mint_fungible(MINIMUM_LIQUIDITY);
+                self.locked_lp.deposit(locked_lp);
 
-            // get the bucket/pool ratios
-            let a_ratio = self.get_pool_ratio(a_resource, a_amount);
-            let b_ratio = self.get_pool_ratio(b_resource, b_amount);
+                (liquidity - MINIMUM_LIQUIDITY, a_amount, b_amount)
+            } else {
+                // only the amounts that match the current pool ratio are deposited
+                let b_optimal = (a_amount * b_balance) / a_balance;
+                let (a_used, b_used) = if b_optimal <= b_amount {
+                    (a_amount, b_optimal)
+                } else {
+                    ((b_amount * a_balance) / b_balance, b_amount)
+                };
+
+                // the amount of new lp tokens is proportional to the share of the pool provided
+                let a_liquidity = (a_used * lp_supply) / a_balance;
+                let b_liquidity = (b_used * lp_supply) / b_balance;
+                (a_liquidity.min(b_liquidity), a_used, b_used)
+            };
+            assert!(!new_lp_amount.is_zero(), "Insufficient liquidity provided");
 
-            // the amount of new lp tokens are proportional to the bucket-pool ratios
-            let new_lp_amount = a_ratio * a_amount + b_ratio * b_amount;
+            // add the liquidity to the pool
+            let a_deposit = a_bucket.take(a_used);
+            let b_deposit = b_bucket.take(b_used);
+            self.pools.get_mut(&a_resource).unwrap().deposit(a_deposit);
+            self.pools.get_mut(&b_resource).unwrap().deposit(b_deposit);
+
+            // at most one of the sides has tokens left, which are refunded to the user
+            let refund_bucket = if a_bucket.is_empty() {
+                a_bucket.drop_empty();
+                b_bucket
+            } else {
+                b_bucket.drop_empty();
+                a_bucket
+            };
 
             // mint and return the new lp tokens
-            self.lp_resource.mint_fungible(new_lp_amount)
+            let lp_bucket = self.lp_resource.mint_fungible(new_lp_amount);
+
+            emit_event(
+                "liquidity_added",
+                [
+                    ("a_resource", a_resource.to_string()),
+                    ("b_resource", b_resource.to_string()),
+                    ("a_amount", a_used.to_string()),
+                    ("b_amount", b_used.to_string()),
+                    ("lp_minted", new_lp_amount.to_string()),
+                ],
+            );
+            self.emit_sync_event();
+
+            (lp_bucket, refund_bucket)
         }
 
+        // burns the LP tokens, returning the corresponding share of each pool resource
         pub fn remove_liquidity(&mut self, lp_bucket: Bucket) -> (Bucket, Bucket) {
             assert_eq!(lp_bucket.resource_address(), self.lp_resource.resource_address(), "Invalid LP resource");
+            self.check_no_active_flash_loans();
 
             // get the pool information
             let a_resource = self.get_a_resource();
@@ -123,11 +554,17 @@ mod asd {
             let b_resource = self.get_b_resource();
             let b_balance = self.get_pool_balance(b_resource);
 
-            // calculate the amount of tokens to take from each pool
-            let decimals = Amount::from(1_000_000u64);
-            let lp_ratio = (lp_bucket.amount() * decimals) / (self.lp_total_supply() * decimals);
-            let a_amount = (lp_ratio.div_ceil(decimals)) * a_balance;
-            let b_amount = (lp_ratio.div_ceil(decimals)) * b_balance;
+            // calculate the amount of tokens to take from each pool, rounded down in favour of the pool
+            let lp_amount = lp_bucket.amount();
+            let lp_supply = self.lp_total_supply();
+            let a_amount = (lp_amount * a_balance) / lp_supply;
+            let b_amount = (lp_amount * b_balance) / lp_supply;
+            assert!(
+                !a_amount.is_zero() && !b_amount.is_zero(),
+                "Insufficient liquidity burned"
+            );
+
+            self.update_oracle();
 
             // burn the LP tokens
             lp_bucket.burn();
@@ -135,9 +572,55 @@ mod asd {
             // return the pool tokens
             let a_bucket = self.pools.get_mut(&a_resource).unwrap().withdraw(a_amount);
             let b_bucket = self.pools.get_mut(&b_resource).unwrap().withdraw(b_amount);
+
+            emit_event(
+                "liquidity_removed",
+                [
+                    ("a_resource", a_resource.to_string()),
+                    ("b_resource", b_resource.to_string()),
+                    ("a_amount", a_amount.to_string()),
+                    ("b_amount", b_amount.to_string()),
+                    ("lp_burned", lp_amount.to_string()),
+                ],
+            );
+            self.emit_sync_event();
+
             (a_bucket, b_bucket)
         }
 
+        // returns the accumulated price of each resource "epochs_ago" epochs before the current one
+        // the accumulated prices are only meaningful when compared between two epochs, see "twap"
+        pub fn observe(&self, epochs_ago: u64) -> BTreeMap<ResourceAddress, Amount> {
+            self.check_no_active_flash_loans();
+            let epoch = Self::past_epoch(epochs_ago);
+            let (a_price_cumulative, b_price_cumulative) = self.get_cumulative_prices_at(epoch);
+
+            let mut prices = BTreeMap::new();
+            prices.insert(self.get_a_resource(), a_price_cumulative);
+            prices.insert(self.get_b_resource(), b_price_cumulative);
+            prices
+        }
+
+        // returns the time-weighted average price of each resource during the last "window" epochs
+        // each price is expressed in units of the other resource, as a fixed point number (see "PRICE_SCALE")
+        pub fn twap(&self, window: u64) -> BTreeMap<ResourceAddress, Amount> {
+            assert!(window > 0, "The TWAP window must be at least one epoch");
+            self.check_no_active_flash_loans();
+
+            let current_epoch = Consensus::current_epoch();
+            let (a_end, b_end) = self.get_cumulative_prices_at(current_epoch);
+            let (a_start, b_start) = self.get_cumulative_prices_at(Self::past_epoch(window));
+
+            let mut prices = BTreeMap::new();
+            prices.insert(self.get_a_resource(), (a_end - a_start) / Amount::from(window));
+            prices.insert(self.get_b_resource(), (b_end - b_start) / Amount::from(window));
+            prices
+        }
+
+        pub fn get_observations(&self) -> Vec<Observation> {
+            self.observations.clone()
+        }
+
         pub fn get_a_resource(&self) -> ResourceAddress {
             *self.pools.keys().nth(0).unwrap()
         }
@@ -150,7 +633,7 @@ mod asd {
             let mut balances = BTreeMap::new();
 
             for (resource, vault) in &self.pools {
-                balances.insert(resource.clone(), vault.balance());
+                balances.insert(*resource, vault.balance());
             }
 
             balances
@@ -164,14 +647,8 @@ mod asd {
             vault.balance()
         }
 
-        pub fn get_pool_ratio(&self, resource: ResourceAddress, amount: Amount) -> Amount {
-            let balance = self.get_pool_balance(resource);
-
-            if balance.is_zero() {
-                Amount::ONE
-            } else {
-                amount / balance
-            }
+        pub fn get_lp_resource(&self) -> ResourceAddress {
+            self.lp_resource.resource_address()
         }
 
         pub fn lp_total_supply(&self) -> Amount {
@@ -182,6 +659,326 @@ mod asd {
             self.fee
         }
 
+        pub fn curve(&self) -> Curve {
+            self.curve
+        }
+
+        pub fn protocol_fee_share(&self) -> u16 {
+            self.protocol_fee_share
+        }
+
+        pub fn get_protocol_fee_balances(&self) -> BTreeMap<ResourceAddress, Amount> {
+            let mut balances = BTreeMap::new();
+
+            for (resource, vault) in &self.protocol_fees {
+                balances.insert(*resource, vault.balance());
+            }
+
+            balances
+        }
+
+        pub fn get_flash_loan_receipt_resource(&self) -> ResourceAddress {
+            self.flash_loan_receipt_resource.resource_address()
+        }
+
+        pub fn get_admin_badge_resource(&self) -> ResourceAddress {
+            self.admin_badge_resource
+        }
+
+        pub fn get_fee_collector_resource(&self) -> ResourceAddress {
+            self.fee_collector_resource.resource_address()
+        }
+
+        fn perform_swap(
+            &mut self,
+            mut input_bucket: Bucket,
+            output_resource: ResourceAddress,
+            output_amount: Amount,
+        ) -> Bucket {
+            let input_resource = input_bucket.resource_address();
+            let input_amount = input_bucket.amount();
+            self.check_no_active_flash_loans();
+
+            self.update_oracle();
+
+            // keep the protocol share of the fee apart, the rest of the fee stays in the pool for the LP holders
+            let fee_amount = input_bucket.amount() - self.apply_fee(input_bucket.amount());
+            let protocol_fee_amount =
+                (fee_amount * Amount::from(self.protocol_fee_share)) / Amount::from(PER_MIL);
+            if !protocol_fee_amount.is_zero() {
+                let protocol_fee = input_bucket.take(protocol_fee_amount);
+                self.protocol_fees
+                    .get_mut(&input_resource)
+                    .unwrap()
+                    .deposit(protocol_fee);
+            }
+
+            self.pools
+                .get_mut(&input_resource)
+                .unwrap()
+                .deposit(input_bucket);
+            let output_bucket = self
+                .pools
+                .get_mut(&output_resource)
+                .unwrap()
+                .withdraw(output_amount);
+
+            emit_event(
+                "swap",
+                [
+                    ("input_resource", input_resource.to_string()),
+                    ("output_resource", output_resource.to_string()),
+                    ("amount_in", input_amount.to_string()),
+                    ("amount_out", output_amount.to_string()),
+                    ("fee", fee_amount.to_string()),
+                    ("protocol_fee", protocol_fee_amount.to_string()),
+                ],
+            );
+            self.emit_sync_event();
+
+            output_bucket
+        }
+
+        // emits the pool reserves after every change, so indexers can track them without querying the pool
+        fn emit_sync_event(&self) {
+            let a_resource = self.get_a_resource();
+            let b_resource = self.get_b_resource();
+            emit_event(
+                "sync",
+                [
+                    ("a_resource", a_resource.to_string()),
+                    ("b_resource", b_resource.to_string()),
+                    ("a_reserve", self.get_pool_balance(a_resource).to_string()),
+                    ("b_reserve", self.get_pool_balance(b_resource).to_string()),
+                ],
+            );
+        }
+
+        // accumulates the current pool prices for the epochs elapsed since the last observation
+        // it must be called before the pool balances change, so the prices of a transaction only count from the next epoch
+        // making the oracle expensive to manipulate, as the prices must be held across epochs
+        fn update_oracle(&mut self) {
+            let current_epoch = Consensus::current_epoch();
+            let last = self.observations.last().unwrap();
+            if last.epoch >= current_epoch {
+                return;
+            }
+
+            let elapsed = Amount::from(current_epoch - last.epoch);
+            let (a_price, b_price) = self.get_spot_prices();
+            let observation = Observation {
+                epoch: current_epoch,
+                a_price_cumulative: last.a_price_cumulative + a_price * elapsed,
+                b_price_cumulative: last.b_price_cumulative + b_price * elapsed,
+            };
+
+            self.observations.push(observation);
+            if self.observations.len() > MAX_OBSERVATIONS {
+                self.observations.remove(0);
+            }
+        }
+
+        // calculates the accumulated prices at any epoch covered by the observations
+        fn get_cumulative_prices_at(&self, epoch: u64) -> (Amount, Amount) {
+            let index = self
+                .observations
+                .iter()
+                .rposition(|o| o.epoch <= epoch)
+                .unwrap_or_else(|| panic!("Not enough price history to observe epoch {}", epoch));
+            let observation = &self.observations[index];
+            let elapsed = Amount::from(epoch - observation.epoch);
+
+            match self.observations.get(index + 1) {
+                // the price was constant between both observations, so we can interpolate
+                Some(next) => {
+                    let interval = Amount::from(next.epoch - observation.epoch);
+                    let a_delta = next.a_price_cumulative - observation.a_price_cumulative;
+                    let b_delta = next.b_price_cumulative - observation.b_price_cumulative;
+                    (
+                        observation.a_price_cumulative + (a_delta * elapsed) / interval,
+                        observation.b_price_cumulative + (b_delta * elapsed) / interval,
+                    )
+                },
+                // the current prices apply since the last observation
+                None => {
+                    let (a_price, b_price) = self.get_spot_prices();
+                    (
+                        observation.a_price_cumulative + a_price * elapsed,
+                        observation.b_price_cumulative + b_price * elapsed,
+                    )
+                },
+            }
+        }
+
+        // returns the current price of each resource in units of the other resource, or zero if the pool is empty
+        fn get_spot_prices(&self) -> (Amount, Amount) {
+            let a_balance = self.get_pool_balance(self.get_a_resource());
+            let b_balance = self.get_pool_balance(self.get_b_resource());
+            if a_balance.is_zero() || b_balance.is_zero() {
+                return (Amount::zero(), Amount::zero());
+            }
+
+            let scale = Amount::from(PRICE_SCALE);
+            (
+                self.get_spot_output_amount(scale, a_balance, b_balance),
+                self.get_spot_output_amount(scale, b_balance, a_balance),
+            )
+        }
+
+        // calculates the output of a swap at the current pool price, as if there was no price impact
+        fn get_spot_output_amount(
+            &self,
+            input_amount: Amount,
+            input_pool_balance: Amount,
+            output_pool_balance: Amount,
+        ) -> Amount {
+            match self.curve {
+                Curve::ConstantProduct => (input_amount * output_pool_balance) / input_pool_balance,
+                Curve::Stable(amplification) => {
+                    let (numerator, denominator) =
+                        stable_swap::spot_price(input_pool_balance, output_pool_balance, amplification);
+                    (input_amount * numerator) / denominator
+                },
+            }
+        }
+
+        fn past_epoch(epochs_ago: u64) -> u64 {
+            let current_epoch = Consensus::current_epoch();
+            current_epoch
+                .checked_sub(epochs_ago)
+                .unwrap_or_else(|| panic!("Not enough price history to observe {} epochs ago", epochs_ago))
+        }
+
+        // calculates the amount of output tokens to return to the user for an amount of input tokens
+        fn get_amount_out(
+            &self,
+            input_resource: ResourceAddress,
+            output_resource: ResourceAddress,
+            input_amount: Amount,
+        ) -> Amount {
+            let (input_pool_balance, output_pool_balance) =
+                self.get_swap_balances(input_resource, output_resource);
+
+            // apply the fee to the input amount
+            // so the user will get a lesser amount of tokens than the theoretical (for the gain of the LP holders)
+            let effective_input_amount = self.apply_fee(input_amount);
+
+            // recalculate the new vault balances for the swap
+            let new_input_pool_balance = input_pool_balance + effective_input_amount;
+            match self.curve {
+                // constant product AMM formula is "k = a * b"
+                // so the new output vault balance should be "b = k / a", which simplifies to the expression below
+                // the result is rounded down, so any rounding error is in favour of the pool
+                Curve::ConstantProduct => {
+                    (effective_input_amount * output_pool_balance) / new_input_pool_balance
+                },
+                // the new output vault balance is the one that keeps the invariant
+                // one token is subtracted, so any rounding error is in favour of the pool
+                Curve::Stable(amplification) => {
+                    let d = stable_swap::compute_d(input_pool_balance, output_pool_balance, amplification);
+                    let new_output_pool_balance =
+                        stable_swap::compute_y(new_input_pool_balance, d, amplification) + Amount::ONE;
+                    output_pool_balance
+                        .checked_sub(new_output_pool_balance)
+                        .unwrap_or_default()
+                },
+            }
+        }
+
+        // calculates the amount of input tokens needed to get an exact amount of output tokens
+        fn get_amount_in(
+            &self,
+            input_resource: ResourceAddress,
+            output_resource: ResourceAddress,
+            output_amount: Amount,
+        ) -> Amount {
+            let (input_pool_balance, output_pool_balance) =
+                self.get_swap_balances(input_resource, output_resource);
+            assert!(
+                output_amount < output_pool_balance,
+                "Insufficient liquidity in the pool for resource '{}'",
+                output_resource
+            );
+
+            let new_output_pool_balance = output_pool_balance - output_amount;
+            let effective_input_amount = match self.curve {
+                // inverse of the constant product formula, rounded up in favour of the pool
+                Curve::ConstantProduct => {
+                    (input_pool_balance * output_amount).div_ceil(new_output_pool_balance)
+                },
+                // the invariant is symmetric, so the new input balance is calculated in the same way as the output one
+                Curve::Stable(amplification) => {
+                    let d = stable_swap::compute_d(input_pool_balance, output_pool_balance, amplification);
+                    let new_input_pool_balance = stable_swap::compute_y(new_output_pool_balance, d, amplification);
+                    (new_input_pool_balance + Amount::ONE).checked_sub(input_pool_balance).unwrap_or_default()
+                },
+            };
+
+            // add back the fee that will be taken from the input
+            let per_mil = Amount::from(PER_MIL);
+            (effective_input_amount * per_mil).div_ceil(per_mil - Amount::from(self.fee))
+        }
+
+        // returns the pool balances for a swap, checking that the swap is possible
+        fn get_swap_balances(
+            &self,
+            input_resource: ResourceAddress,
+            output_resource: ResourceAddress,
+        ) -> (Amount, Amount) {
+            // check that the parameters are correct
+            self.check_pool_resources(input_resource, output_resource);
+
+            let input_pool_balance = self.get_pool_balance(input_resource);
+            let output_pool_balance = self.get_pool_balance(output_resource);
+
+            // check that the pools are not empty, to prevent division by 0 errors later
+            assert!(
+                !input_pool_balance.is_zero(),
+                "The pool for resource '{}' is empty",
+                input_resource
+            );
+            assert!(
+                !output_pool_balance.is_zero(),
+                "The pool for resource '{}' is empty",
+                output_resource
+            );
+
+            (input_pool_balance, output_pool_balance)
+        }
+
+        // returns the amount that remains after taking the pool fee
+        fn apply_fee(&self, amount: Amount) -> Amount {
+            amount - (amount * Amount::from(self.fee)) / Amount::from(PER_MIL)
+        }
+
+        fn check_fee(fee: u16) {
+            assert!(fee <= MAX_FEE, "Invalid fee {}, it must be at most {} per-mil", fee, MAX_FEE);
+        }
+
+        fn get_other_resource(&self, resource: ResourceAddress) -> ResourceAddress {
+            assert!(
+                self.pools.contains_key(&resource),
+                "The resource {} is not in the pool",
+                resource
+            );
+            *self.pools.keys().find(|r| **r != resource).unwrap()
+        }
+
+        fn check_deadline(valid_until_epoch: u64) {
+            let current_epoch = Consensus::current_epoch();
+            assert!(
+                current_epoch <= valid_until_epoch,
+                "Swap deadline has passed: current epoch {} is after {}",
+                current_epoch,
+                valid_until_epoch
+            );
+        }
+
+        // the pool balances are not reliable while a flash loan is active
+        fn check_no_active_flash_loans(&self) {
+            assert_eq!(self.active_flash_loans, 0, "Operation not allowed while a flash loan is active");
+        }
+
         fn check_pool_resources(&self, a_resource: ResourceAddress, b_resource: ResourceAddress) {
             assert!(
                 a_resource != b_resource,
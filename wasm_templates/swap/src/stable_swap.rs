@@ -0,0 +1,165 @@
+// Copyright 2024 The Tari Project
+// SPDX-License-Identifier: BSD-3-Clause
+
+// Math for the StableSwap invariant of a two resource pool:
+//   "A * n^n * (x + y) + D = A * D * n^n + D^(n+1) / (n^n * x * y)"
+// where "A" is the amplification coefficient and "D" the total amount of tokens when the price is 1:1.
+// It behaves like the constant sum "x + y = D" around the 1:1 price, and like the constant product far from it
+
+use tari_template_lib::types::Amount;
+
+// number of resources in the pool
+const N: u128 = 2;
+
+// maximum number of Newton iterations, the calculations usually converge in less than 10
+const MAX_ITERATIONS: usize = 255;
+
+const OVERFLOW: &str = "StableSwap calculation overflow";
+
+// calculates the invariant "D" for the pool balances
+pub fn compute_d(x: Amount, y: Amount, amplification: u64) -> Amount {
+    let (x, y) = (x.to_u128(), y.to_u128());
+    let sum = x.checked_add(y).expect(OVERFLOW);
+    if sum == 0 {
+        return Amount::zero();
+    }
+
+    let ann = u128::from(amplification) * N;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // "D^(n+1) / (n^n * x * y)", calculated step by step to avoid overflows
+        let mut d_p = d;
+        d_p = mul_div(d_p, d, x.checked_mul(N).expect(OVERFLOW));
+        d_p = mul_div(d_p, d, y.checked_mul(N).expect(OVERFLOW));
+
+        let d_prev = d;
+        let numerator = ann
+            .checked_mul(sum)
+            .and_then(|n| n.checked_add(d_p.checked_mul(N)?))
+            .expect(OVERFLOW);
+        let denominator = (ann - 1)
+            .checked_mul(d)
+            .and_then(|n| n.checked_add(d_p.checked_mul(N + 1)?))
+            .expect(OVERFLOW);
+        d = mul_div(numerator, d, denominator);
+        if d.abs_diff(d_prev) <= 1 {
+            return Amount::new(d);
+        }
+    }
+
+    panic!("The StableSwap invariant did not converge");
+}
+
+// calculates the balance of one resource that keeps the invariant "d", given the balance of the other resource
+pub fn compute_y(x: Amount, d: Amount, amplification: u64) -> Amount {
+    let (x, d) = (x.to_u128(), d.to_u128());
+    let ann = u128::from(amplification) * N;
+
+    // "y^2 + (b - D) * y = c", solved with Newton's method
+    let mut c = d;
+    c = mul_div(c, d, x.checked_mul(N).expect(OVERFLOW));
+    c = mul_div(c, d, ann * N);
+    let b = x.checked_add(d / ann).expect(OVERFLOW);
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let numerator = y
+            .checked_mul(y)
+            .and_then(|n| n.checked_add(c))
+            .expect(OVERFLOW);
+        let denominator = y
+            .checked_mul(2)
+            .and_then(|n| n.checked_add(b))
+            .and_then(|n| n.checked_sub(d))
+            .expect(OVERFLOW);
+        y = numerator / denominator;
+        if y.abs_diff(y_prev) <= 1 {
+            return Amount::new(y);
+        }
+    }
+
+    panic!("The StableSwap invariant did not converge");
+}
+
+// calculates the marginal price of the input resource in units of the output resource
+// returned as a fraction, scaled down so it can be safely multiplied by an amount
+pub fn spot_price(input_balance: Amount, output_balance: Amount, amplification: u64) -> (Amount, Amount) {
+    let d = compute_d(input_balance, output_balance, amplification).to_u128();
+    let (x, y) = (input_balance.to_u128(), output_balance.to_u128());
+    let ann = u128::from(amplification) * N;
+
+    // derivative of the invariant: "(4 * Ann * x * y + D^3 / x) / (4 * Ann * x * y + D^3 / y)"
+    // both parts of the fraction are divided by D, so they do not overflow
+    let product_term = mul_div(x, y, d).checked_mul(2 * N * ann).expect(OVERFLOW);
+    let mut numerator = product_term.checked_add(mul_div(d, d, x)).expect(OVERFLOW);
+    let mut denominator = product_term.checked_add(mul_div(d, d, y)).expect(OVERFLOW);
+
+    // keep both parts of the fraction small, the lost precision is negligible
+    while numerator > u64::MAX as u128 || denominator > u64::MAX as u128 {
+        numerator >>= 1;
+        denominator >>= 1;
+    }
+
+    (Amount::new(numerator), Amount::new(denominator))
+}
+
+// calculates "a * b / c" rounded down, with a 256-bit intermediate product so only the result needs to fit in u128
+fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    assert!(c != 0, "StableSwap division by zero");
+    let (hi, lo) = widening_mul(a, b);
+    if hi == 0 {
+        return lo / c;
+    }
+    assert!(hi < c, "{}", OVERFLOW);
+
+    // binary long division, the remainder is always lower than "c" before each shift
+    let mut remainder = hi;
+    let mut quotient = 0u128;
+    for i in (0..u128::BITS).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((lo >> i) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    quotient
+}
+
+// returns the high and low halves of the 256-bit product "a * b"
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+
+    let mid = (lo_lo >> 64) + (hi_lo & MASK) + (lo_hi & MASK);
+    let lo = (lo_lo & MASK) | (mid << 64);
+    let hi = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, 3, 6), u128::MAX >> 1);
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), 1 << 110);
+        assert_eq!(mul_div(7, 3, 2), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "StableSwap calculation overflow")]
+    fn test_mul_div_overflow() {
+        mul_div(u128::MAX, 2, 1);
+    }
+}
@@ -0,0 +1,26 @@
+use tari_template_lib::prelude::*;
+
+// Helper template used by the pool tests to create the fungible resources to be swapped,
+// and to try to mint and burn resources from outside the pool component
+#[template]
+mod test_tokens {
+    use super::*;
+
+    pub struct TestTokens {}
+
+    impl TestTokens {
+        pub fn create(symbol: String, initial_supply: Amount) -> Bucket {
+            ResourceBuilder::public_fungible()
+                .with_token_symbol(symbol)
+                .initial_supply(initial_supply)
+        }
+
+        pub fn mint(resource: ResourceAddress, amount: Amount) -> Bucket {
+            ResourceManager::get(resource).mint_fungible(amount)
+        }
+
+        pub fn burn(bucket: Bucket) {
+            bucket.burn();
+        }
+    }
+}
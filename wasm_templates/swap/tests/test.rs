@@ -0,0 +1,1314 @@
+use std::collections::BTreeMap;
+use tari_template_lib::models::BucketId;
+use tari_template_lib::prelude::{Amount, ComponentAddress, ResourceAddress, ResourceAuthAction};
+use tari_template_lib::types::NonFungibleAddress;
+use tari_template_test_tooling::crypto::RistrettoSecretKey;
+use tari_template_test_tooling::engine_types::commit_result::ExecuteResult;
+use tari_template_test_tooling::engine_types::events::Event;
+use tari_template_test_tooling::engine_types::virtual_substate::{VirtualSubstate, VirtualSubstateId};
+use tari_template_test_tooling::support::assert_error::{
+    assert_access_denied_for_action, assert_reject_reason,
+};
+use tari_template_test_tooling::transaction::{args, Transaction};
+use tari_template_test_tooling::TemplateTest;
+
+const TEMPLATE_MODULE_NAME: &str = "{{ project-name | upper_camel_case }}Pool";
+const POOL_FEE: u16 = 3;
+const MAX_FEE: u16 = 100;
+const PRICE_SCALE: u128 = 1_000_000_000_000;
+const LIQUIDITY: u128 = 100_000;
+const LARGE_LIQUIDITY: u128 = 100_000_000_000_000_000;
+const MAX_AMPLIFICATION: u64 = 10_000;
+
+#[test]
+fn it_swaps_with_minimum_output() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // 1000 A are worth ~997 B after the fee, minus the price impact
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, 900, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_pool_balance", args![b_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let b_pool_balance = result.finalize.execution_results[5]
+        .decode::<Amount>()
+        .unwrap();
+    // output = (997 * 100_000) / (100_000 + 997) = 987
+    assert_eq!(b_pool_balance, 100_000 - 987);
+}
+
+#[test]
+fn it_rejects_swaps_below_minimum_output() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, 1_000, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Insufficient output amount");
+}
+
+#[test]
+fn it_rejects_expired_swaps() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(11));
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, 0, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Swap deadline has passed");
+}
+
+#[test]
+fn it_swaps_for_exact_output() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "balance", args![a_resource])
+            .call_method(account, "withdraw", args![a_resource, 2_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_out",
+                args![Workspace("input"), b_resource, 987, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output.0")])
+            .call_method(account, "deposit", args![Workspace("output.1")])
+            .call_method(account, "balance", args![a_resource])
+            .call_method(pool_component, "get_pool_balance", args![b_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let a_balance_before = result.finalize.execution_results[0]
+        .decode::<Amount>()
+        .unwrap();
+    let a_balance_after = result.finalize.execution_results[7]
+        .decode::<Amount>()
+        .unwrap();
+    let b_pool_balance = result.finalize.execution_results[8]
+        .decode::<Amount>()
+        .unwrap();
+    // only the input needed for the exact output is taken, the rest is refunded
+    assert_eq!(a_balance_before - a_balance_after, 1_000);
+    assert_eq!(b_pool_balance, 100_000 - 987);
+}
+
+#[test]
+fn it_rejects_exact_output_swaps_with_insufficient_input() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 500])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_out",
+                args![Workspace("input"), b_resource, 987, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output.0")])
+            .call_method(account, "deposit", args![Workspace("output.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Excessive input amount");
+}
+
+#[test]
+fn it_quotes_swaps() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account_proof,
+        account_key,
+        a_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "quote", args![a_resource, 1_000])
+            .call_method(pool_component, "quote", args![a_resource, 10_000])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let (output_amount, fee_amount, price_impact) = result.finalize.execution_results[0]
+        .decode::<(Amount, Amount, u16)>()
+        .unwrap();
+    assert_eq!(output_amount, 987);
+    assert_eq!(fee_amount, 3);
+    // (997 - 987) / 997 = ~1%
+    assert_eq!(price_impact, 100);
+
+    // bigger swaps move the price further
+    let (_, _, price_impact) = result.finalize.execution_results[1]
+        .decode::<(Amount, Amount, u16)>()
+        .unwrap();
+    assert!(price_impact > 800);
+}
+
+#[test]
+fn it_locks_minimum_liquidity_on_first_deposit() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        lp_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "balance", args![lp_resource])
+            .call_method(pool_component, "lp_total_supply", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // sqrt(100_000 * 100_000) minus the locked minimum liquidity
+    let lp_balance = result.finalize.execution_results[0]
+        .decode::<Amount>()
+        .unwrap();
+    let lp_total_supply = result.finalize.execution_results[1]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(lp_balance, 99_000);
+    assert_eq!(lp_total_supply, 100_000);
+}
+
+#[test]
+fn it_mints_lp_tokens_proportionally_and_refunds_the_excess() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        lp_resource,
+        ..
+    } = setup();
+
+    // the pool ratio is 1:1, so half of the B tokens are not needed
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 20_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                pool_component,
+                "add_liquidity",
+                args![Workspace("a"), Workspace("b")],
+            )
+            .put_last_instruction_output_on_workspace("liquidity")
+            .assert_bucket_contains_exactly("liquidity.0", lp_resource, 10_000u64)
+            .assert_bucket_contains_exactly("liquidity.1", b_resource, 10_000u64)
+            .call_method(account, "deposit", args![Workspace("liquidity.0")])
+            .call_method(account, "deposit", args![Workspace("liquidity.1")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let balances = result.finalize.execution_results[10]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(balances[&a_resource], 110_000);
+    assert_eq!(balances[&b_resource], 110_000);
+}
+
+#[test]
+fn it_removes_liquidity_pro_rata() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        lp_resource,
+        ..
+    } = setup();
+
+    // grow the B side of the pool, so the resources are no longer 1:1
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![b_resource, 25_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), a_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    // 10% of the LP supply
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "get_pool_balances", args![])
+            .call_method(account, "withdraw", args![lp_resource, 10_000])
+            .put_last_instruction_output_on_workspace("lp")
+            .call_method(pool_component, "remove_liquidity", args![Workspace("lp")])
+            .put_last_instruction_output_on_workspace("liquidity")
+            .call_method(account, "deposit", args![Workspace("liquidity.0")])
+            .call_method(account, "deposit", args![Workspace("liquidity.1")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .call_method(pool_component, "lp_total_supply", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let balances_before = result.finalize.execution_results[0]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    let balances_after = result.finalize.execution_results[7]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    let lp_total_supply = result.finalize.execution_results[8]
+        .decode::<Amount>()
+        .unwrap();
+    for resource in [a_resource, b_resource] {
+        let withdrawn = balances_before[&resource] - balances_after[&resource];
+        assert_eq!(withdrawn, balances_before[&resource] / 10);
+    }
+    assert_eq!(lp_total_supply, 90_000);
+}
+
+#[test]
+fn it_prevents_outside_callers_from_minting_lp_tokens() {
+    let TestSetup {
+        mut test,
+        account,
+        account_proof,
+        account_key,
+        lp_resource,
+        ..
+    } = setup();
+
+    // even the signer that created the pool cannot mint LP tokens
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address("TestTokens"),
+                "mint",
+                args![lp_resource, 1_000_000],
+            )
+            .put_last_instruction_output_on_workspace("lp")
+            .call_method(account, "deposit", args![Workspace("lp")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_access_denied_for_action(reason, ResourceAuthAction::Mint);
+}
+
+#[test]
+fn it_prevents_outside_callers_from_burning_lp_tokens() {
+    let TestSetup {
+        mut test,
+        account,
+        account_proof,
+        account_key,
+        lp_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![lp_resource, 1_000])
+            .put_last_instruction_output_on_workspace("lp")
+            .call_function(
+                test.get_template_address("TestTokens"),
+                "burn",
+                args![Workspace("lp")],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_access_denied_for_action(reason, ResourceAuthAction::Burn);
+}
+
+#[test]
+fn it_allows_the_admin_to_set_the_fee() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        admin_badge_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(pool_component, "set_fee", args![10])
+            .drop_all_proofs_in_workspace()
+            .call_method(pool_component, "fee", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let fee = result.finalize.execution_results[4].decode::<u16>().unwrap();
+    assert_eq!(fee, 10);
+
+    let event = find_event(&result, "set_fee");
+    assert_eq!(event.get_payload("old_fee"), Some(POOL_FEE.to_string().as_str()));
+    assert_eq!(event.get_payload("fee"), Some("10"));
+}
+
+#[test]
+fn it_rejects_invalid_fees() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        admin_badge_resource,
+        ..
+    } = setup();
+
+    let set_fee = |fee: u16| {
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(pool_component, "set_fee", args![fee])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&account_key)
+    };
+
+    // the fee is capped at 10%
+    test.execute_expect_success(set_fee(MAX_FEE), vec![account_proof.clone()]);
+    for fee in [MAX_FEE + 1, 1000] {
+        let reason = test.execute_expect_failure(set_fee(fee), vec![account_proof.clone()]);
+        assert_reject_reason(reason, format!("Invalid fee {fee}, it must be at most {MAX_FEE} per-mil"));
+    }
+
+    // the cap also applies to new pools
+    let template = test.get_template_address(TEMPLATE_MODULE_NAME);
+    let new_pools = [
+        ("new", args![a_resource, b_resource, MAX_FEE + 1]),
+        ("new_stable", args![a_resource, b_resource, MAX_FEE + 1, 100u64]),
+    ];
+    for (function, args) in new_pools {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_function(template, function, args)
+                .put_last_instruction_output_on_workspace("pool")
+                .call_method(account, "deposit", args![Workspace("pool.1")])
+                .build_and_seal(&account_key),
+            vec![account_proof.clone()],
+        );
+        assert_reject_reason(reason, "Invalid fee 101");
+    }
+}
+
+#[test]
+fn it_prevents_non_admins_from_changing_fees() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        ..
+    } = setup();
+    let (_, user_proof, user_key) = test.create_empty_account();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "set_fee", args![100])
+            .build_and_seal(&user_key),
+        vec![user_proof.clone()],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "set_protocol_fee_share", args![500])
+            .build_and_seal(&user_key),
+        vec![user_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+}
+
+#[test]
+fn it_collects_and_withdraws_protocol_fees() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        admin_badge_resource,
+        fee_collector_resource,
+        ..
+    } = setup();
+
+    // keep half of the fees for the protocol, and issue a fee collector badge to a separate account
+    let (collector_account, collector_proof, collector_key) = test.create_empty_account();
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(account, admin_badge_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(pool_component, "set_protocol_fee_share", args![500])
+            .call_method(pool_component, "create_fee_collector_badge", args![])
+            .put_last_instruction_output_on_workspace("badge")
+            .call_method(collector_account, "deposit", args![Workspace("badge")])
+            .drop_all_proofs_in_workspace()
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    // the fee of swapping 10_000 A is 30 A, so 15 A go to the protocol
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_protocol_fee_balances", args![])
+            .call_method(pool_component, "get_pool_balance", args![a_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let protocol_fees = result.finalize.execution_results[5]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(protocol_fees[&a_resource], 15);
+    assert_eq!(protocol_fees[&b_resource], 0);
+    let a_pool_balance = result.finalize.execution_results[6]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(a_pool_balance, 100_000 + 10_000 - 15);
+
+    // only the fee collector can withdraw the protocol fees
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "withdraw_protocol_fees", args![a_resource])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(account, "deposit", args![Workspace("fees")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    assert_reject_reason(reason, "Access Denied");
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .create_proof(collector_account, fee_collector_resource)
+            .put_last_instruction_output_on_workspace("proof")
+            .call_method(pool_component, "withdraw_protocol_fees", args![a_resource])
+            .put_last_instruction_output_on_workspace("fees")
+            .call_method(collector_account, "deposit", args![Workspace("fees")])
+            .drop_all_proofs_in_workspace()
+            .call_method(collector_account, "balance", args![a_resource])
+            .build_and_seal(&collector_key),
+        vec![collector_proof],
+    );
+    let collector_balance = result.finalize.execution_results[6]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(collector_balance, 15);
+
+    let event = find_event(&result, "withdraw_protocol_fees");
+    assert_eq!(event.get_payload("amount"), Some("15"));
+}
+
+#[test]
+fn it_computes_time_weighted_average_prices() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // the pool keeps a 1:1 price for 10 epochs, and then a swap changes it
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(10));
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let balances = result.finalize.execution_results[5]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    let a_balance = balances[&a_resource].to_u128();
+    let b_balance = balances[&b_resource].to_u128();
+    let a_price = b_balance * PRICE_SCALE / a_balance;
+    let b_price = a_balance * PRICE_SCALE / b_balance;
+
+    // the new price is held for another 10 epochs
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(20));
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "twap", args![20])
+            .call_method(pool_component, "twap", args![10])
+            .call_method(pool_component, "observe", args![15])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let full_window = result.finalize.execution_results[0]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(full_window[&a_resource], (10 * PRICE_SCALE + 10 * a_price) / 20);
+    assert_eq!(full_window[&b_resource], (10 * PRICE_SCALE + 10 * b_price) / 20);
+
+    let last_window = result.finalize.execution_results[1]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(last_window[&a_resource], a_price);
+    assert_eq!(last_window[&b_resource], b_price);
+
+    // 15 epochs ago only 5 epochs at the initial price had been accumulated
+    let cumulative = result.finalize.execution_results[2]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(cumulative[&a_resource], 5 * PRICE_SCALE);
+    assert_eq!(cumulative[&b_resource], 5 * PRICE_SCALE);
+}
+
+#[test]
+fn it_rejects_twap_windows_without_price_history() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account_proof,
+        account_key,
+        ..
+    } = setup();
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(5));
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "twap", args![10])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Not enough price history");
+}
+
+#[test]
+fn it_emits_swap_events() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // output = (997 * 100_000) / (100_000 + 997) = 987
+    let event = find_event(&result, "swap");
+    assert_eq!(event.get_payload("input_resource"), Some(a_resource.to_string().as_str()));
+    assert_eq!(event.get_payload("output_resource"), Some(b_resource.to_string().as_str()));
+    assert_eq!(event.get_payload("amount_in"), Some("1000"));
+    assert_eq!(event.get_payload("amount_out"), Some("987"));
+    assert_eq!(event.get_payload("fee"), Some("3"));
+    assert_eq!(event.get_payload("protocol_fee"), Some("0"));
+
+    let event = find_event(&result, "sync");
+    assert_eq!(sync_reserve(event, a_resource), "101000");
+    assert_eq!(sync_reserve(event, b_resource), "99013");
+}
+
+#[test]
+fn it_emits_liquidity_events() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        lp_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 20_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                pool_component,
+                "add_liquidity",
+                args![Workspace("a"), Workspace("b")],
+            )
+            .put_last_instruction_output_on_workspace("liquidity")
+            .call_method(account, "deposit", args![Workspace("liquidity.0")])
+            .call_method(account, "deposit", args![Workspace("liquidity.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    // the excess B tokens are refunded, so only 10_000 of each are added
+    let event = find_event(&result, "liquidity_added");
+    assert_eq!(event.get_payload("a_amount"), Some("10000"));
+    assert_eq!(event.get_payload("b_amount"), Some("10000"));
+    assert_eq!(event.get_payload("lp_minted"), Some("10000"));
+    let event = find_event(&result, "sync");
+    assert_eq!(sync_reserve(event, a_resource), "110000");
+    assert_eq!(sync_reserve(event, b_resource), "110000");
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![lp_resource, 11_000])
+            .put_last_instruction_output_on_workspace("lp")
+            .call_method(pool_component, "remove_liquidity", args![Workspace("lp")])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(account, "deposit", args![Workspace("tokens.0")])
+            .call_method(account, "deposit", args![Workspace("tokens.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let event = find_event(&result, "liquidity_removed");
+    assert_eq!(event.get_payload("a_amount"), Some("11000"));
+    assert_eq!(event.get_payload("b_amount"), Some("11000"));
+    assert_eq!(event.get_payload("lp_burned"), Some("11000"));
+    let event = find_event(&result, "sync");
+    assert_eq!(sync_reserve(event, a_resource), "99000");
+    assert_eq!(sync_reserve(event, b_resource), "99000");
+}
+
+#[test]
+fn it_repays_flash_loans_with_a_fee() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        ..
+    } = setup();
+
+    // the fee for borrowing 10_000 A is 30 A, paid from the account funds
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "flash_loan", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("loan")
+            .call_method(account, "deposit", args![Workspace("loan.0")])
+            .call_method(account, "withdraw", args![a_resource, 10_030])
+            .put_last_instruction_output_on_workspace("repayment")
+            .call_method(
+                pool_component,
+                "repay_flash_loan",
+                args![Workspace("loan.1"), Workspace("repayment")],
+            )
+            .put_last_instruction_output_on_workspace("change")
+            .call_method(account, "deposit", args![Workspace("change")])
+            .call_method(pool_component, "get_pool_balance", args![a_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    let a_pool_balance = result.finalize.execution_results[8]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(a_pool_balance, 100_030);
+
+    let event = find_event(&result, "flash_loan_repaid");
+    assert_eq!(event.get_payload("amount"), Some("10000"));
+    assert_eq!(event.get_payload("fee"), Some("30"));
+}
+
+#[test]
+fn it_rejects_insufficient_flash_loan_repayments() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "flash_loan", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("loan")
+            .call_method(
+                pool_component,
+                "repay_flash_loan",
+                args![Workspace("loan.1"), Workspace("loan.0")],
+            )
+            .put_last_instruction_output_on_workspace("change")
+            .call_method(account, "deposit", args![Workspace("change")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Insufficient flash loan repayment");
+}
+
+#[test]
+fn it_rejects_unpaid_flash_loans() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        ..
+    } = setup();
+
+    // the loan receipt cannot be deposited, so the loan must be repaid
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "flash_loan", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("loan")
+            .call_method(account, "deposit", args![Workspace("loan.0")])
+            .call_method(account, "deposit", args![Workspace("loan.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_access_denied_for_action(reason, ResourceAuthAction::Deposit);
+}
+
+#[test]
+fn it_prevents_swaps_during_flash_loans() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "flash_loan", args![b_resource, 50_000])
+            .put_last_instruction_output_on_workspace("loan")
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(pool_component, "swap", args![Workspace("input"), b_resource])
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(
+                pool_component,
+                "repay_flash_loan",
+                args![Workspace("loan.1"), Workspace("loan.0")],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Operation not allowed while a flash loan is active");
+}
+
+#[test]
+fn it_prevents_price_reads_during_flash_loans() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // the borrowed reserves would otherwise skew the spot price used by quotes and the oracle
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(10));
+    let price_reads = [
+        ("twap", args![10]),
+        ("observe", args![0]),
+        ("quote", args![a_resource, 1_000]),
+    ];
+    for (method, args) in price_reads {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_method(pool_component, "flash_loan", args![b_resource, 50_000])
+                .put_last_instruction_output_on_workspace("loan")
+                .call_method(pool_component, method, args)
+                .call_method(
+                    pool_component,
+                    "repay_flash_loan",
+                    args![Workspace("loan.1"), Workspace("loan.0")],
+                )
+                .build_and_seal(&account_key),
+            vec![account_proof.clone()],
+        );
+        assert_reject_reason(reason, "Operation not allowed while a flash loan is active");
+    }
+}
+
+#[test]
+fn it_checkpoints_the_oracle_on_flash_loans() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // the 1:1 price is accumulated up to the loan, before the fee changes the reserves
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(10));
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "flash_loan", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("loan")
+            .call_method(account, "deposit", args![Workspace("loan.0")])
+            .call_method(account, "withdraw", args![a_resource, 10_030])
+            .put_last_instruction_output_on_workspace("repayment")
+            .call_method(
+                pool_component,
+                "repay_flash_loan",
+                args![Workspace("loan.1"), Workspace("repayment")],
+            )
+            .put_last_instruction_output_on_workspace("change")
+            .call_method(account, "deposit", args![Workspace("change")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+
+    test.set_virtual_substate(VirtualSubstateId::CurrentEpoch, VirtualSubstate::CurrentEpoch(20));
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "observe", args![10])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    let cumulative = result.finalize.execution_results[0]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    assert_eq!(cumulative[&a_resource], 10 * PRICE_SCALE);
+    assert_eq!(cumulative[&b_resource], 10 * PRICE_SCALE);
+}
+
+#[test]
+fn it_swaps_with_low_slippage_in_stable_pools() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup_stable(100);
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "quote", args![a_resource, 10_000])
+            .call_method(account, "withdraw", args![a_resource, 10_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, 9_900, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_pool_balance", args![b_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // a constant product pool would only return 9_066 B, and the fee alone takes 30 A
+    let (output_amount, fee_amount, price_impact) = result.finalize.execution_results[0]
+        .decode::<(Amount, Amount, u16)>()
+        .unwrap();
+    assert!(output_amount > 9_900 && output_amount < 9_970);
+    assert_eq!(fee_amount, 30);
+    assert!(price_impact < 100);
+
+    let b_pool_balance = result.finalize.execution_results[6]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(b_pool_balance, Amount::from(100_000u64) - output_amount);
+}
+
+#[test]
+fn it_swaps_for_exact_output_in_stable_pools() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup_stable(100);
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 6_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_out",
+                args![Workspace("input"), b_resource, 5_000, 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output.0")])
+            .call_method(account, "deposit", args![Workspace("output.1")])
+            .call_method(pool_component, "get_pool_balances", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // the input must at least cover the output plus the fee, and it is close to 1:1
+    let balances = result.finalize.execution_results[6]
+        .decode::<BTreeMap<ResourceAddress, Amount>>()
+        .unwrap();
+    let input_amount = balances[&a_resource] - Amount::from(100_000u64);
+    assert!(input_amount > 5_015 && input_amount < 5_030);
+    assert_eq!(balances[&b_resource], 95_000);
+}
+
+#[test]
+fn it_swaps_with_large_reserves_and_maximum_amplification() {
+    let TestSetup {
+        mut test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup_pool(Some(MAX_AMPLIFICATION), LARGE_LIQUIDITY);
+
+    // the intermediate products of the invariant are larger than u128 with these reserves
+    let input_amount = Amount::new(LARGE_LIQUIDITY / 100);
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(pool_component, "quote", args![a_resource, input_amount])
+            .call_method(account, "withdraw", args![a_resource, input_amount])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                pool_component,
+                "swap_exact_in",
+                args![Workspace("input"), b_resource, Amount::zero(), 10],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(pool_component, "get_pool_balance", args![b_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // the swap is almost 1:1 after the 0.3% fee
+    let (output_amount, _, price_impact) = result.finalize.execution_results[0]
+        .decode::<(Amount, Amount, u16)>()
+        .unwrap();
+    let max_output = input_amount.to_u128() * 997 / 1000;
+    assert!(output_amount.to_u128() > max_output - max_output / 10_000);
+    assert!(output_amount.to_u128() <= max_output);
+    assert_eq!(price_impact, 0);
+
+    let b_pool_balance = result.finalize.execution_results[6]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(b_pool_balance, Amount::new(LARGE_LIQUIDITY) - output_amount);
+}
+
+#[test]
+fn it_rejects_invalid_amplification() {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (account, account_proof, account_key) = test.create_funded_account();
+    let supply = Amount::new(LIQUIDITY * 10);
+    let a_resource = create_token(&mut test, account, &account_proof, &account_key, "A", supply);
+    let b_resource = create_token(&mut test, account, &account_proof, &account_key, "B", supply);
+
+    let template = test.get_template_address(TEMPLATE_MODULE_NAME);
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_function(template, "new_stable", args![a_resource, b_resource, POOL_FEE, 0u64])
+            .put_last_instruction_output_on_workspace("pool")
+            .call_method(account, "deposit", args![Workspace("pool.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    assert_reject_reason(reason, "Invalid amplification");
+}
+
+struct TestSetup {
+    test: TemplateTest,
+    pool_component: ComponentAddress,
+    account: ComponentAddress,
+    account_proof: NonFungibleAddress,
+    account_key: RistrettoSecretKey,
+    a_resource: ResourceAddress,
+    b_resource: ResourceAddress,
+    lp_resource: ResourceAddress,
+    admin_badge_resource: ResourceAddress,
+    fee_collector_resource: ResourceAddress,
+}
+
+// Creates a pool with 100_000 tokens of each resource
+fn setup() -> TestSetup {
+    setup_pool(None, LIQUIDITY)
+}
+
+// Creates a StableSwap pool with 100_000 tokens of each resource
+fn setup_stable(amplification: u64) -> TestSetup {
+    setup_pool(Some(amplification), LIQUIDITY)
+}
+
+// Creates a pool with the liquidity amount of each resource, and 10 times that amount of each resource in the account
+fn setup_pool(amplification: Option<u64>, liquidity: u128) -> TestSetup {
+    let mut test = TemplateTest::new(".", [".", "tests/templates/test_tokens"]);
+    let (account, account_proof, account_key) = test.create_funded_account();
+
+    let supply = Amount::new(liquidity * 10);
+    let a_resource = create_token(&mut test, account, &account_proof, &account_key, "A", supply);
+    let b_resource = create_token(&mut test, account, &account_proof, &account_key, "B", supply);
+
+    let template = test.get_template_address(TEMPLATE_MODULE_NAME);
+    let builder = match amplification {
+        Some(amplification) => Transaction::builder_localnet().call_function(
+            template,
+            "new_stable",
+            args![a_resource, b_resource, POOL_FEE, amplification],
+        ),
+        None => Transaction::builder_localnet().call_function(
+            template,
+            "new",
+            args![a_resource, b_resource, POOL_FEE],
+        ),
+    };
+    let result = test.execute_expect_success(
+        builder
+            .put_last_instruction_output_on_workspace("pool")
+            .call_method(account, "deposit", args![Workspace("pool.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let (pool_component, _) = result.finalize.execution_results[0]
+        .decode::<(ComponentAddress, BucketId)>()
+        .unwrap();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, Amount::new(liquidity)])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, Amount::new(liquidity)])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(
+                pool_component,
+                "add_liquidity",
+                args![Workspace("a"), Workspace("b")],
+            )
+            .put_last_instruction_output_on_workspace("liquidity")
+            .call_method(account, "deposit", args![Workspace("liquidity.0")])
+            .call_method(account, "deposit", args![Workspace("liquidity.1")])
+            .call_method(pool_component, "get_lp_resource", args![])
+            .call_method(pool_component, "get_admin_badge_resource", args![])
+            .call_method(pool_component, "get_fee_collector_resource", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let lp_resource = result.finalize.execution_results[8]
+        .decode::<ResourceAddress>()
+        .unwrap();
+    let admin_badge_resource = result.finalize.execution_results[9]
+        .decode::<ResourceAddress>()
+        .unwrap();
+    let fee_collector_resource = result.finalize.execution_results[10]
+        .decode::<ResourceAddress>()
+        .unwrap();
+
+    TestSetup {
+        test,
+        pool_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        lp_resource,
+        admin_badge_resource,
+        fee_collector_resource,
+    }
+}
+
+// events emitted by the pool are prefixed with the template name
+fn find_event<'a>(result: &'a ExecuteResult, topic: &str) -> &'a Event {
+    let topic = format!("{}.{}", TEMPLATE_MODULE_NAME, topic);
+    result
+        .finalize
+        .events
+        .iter()
+        .find(|e| e.topic() == topic)
+        .unwrap_or_else(|| panic!("{} event not emitted", topic))
+}
+
+// the pool resources of the events are sorted by address, so they may not match the A and B of the tests
+fn sync_reserve(event: &Event, resource: ResourceAddress) -> &str {
+    let key = if event.get_payload("a_resource") == Some(resource.to_string().as_str()) {
+        "a_reserve"
+    } else {
+        "b_reserve"
+    };
+    event.get_payload(key).unwrap()
+}
+
+fn create_token(
+    test: &mut TemplateTest,
+    account: ComponentAddress,
+    account_proof: &NonFungibleAddress,
+    account_key: &RistrettoSecretKey,
+    symbol: &str,
+    supply: Amount,
+) -> ResourceAddress {
+    let template = test.get_template_address("TestTokens");
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(template, "create", args![symbol, supply])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(account, "deposit", args![Workspace("tokens")])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+
+    result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap()
+}
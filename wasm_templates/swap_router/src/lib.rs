@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use tari_template_lib::prelude::*;
+
+#[template]
+mod {{ project-name | snake_case }} {
+    use super::*;
+
+    // Factory and router for pools of the swap template
+    // pools are deployed from a single pool template, with at most one pool per resource pair
+    pub struct {{ project-name | upper_camel_case }}Router {
+        pool_template: TemplateAddress,
+        // the pair resources are sorted, so the same pool is found regardless of the swap direction
+        pools: BTreeMap<(ResourceAddress, ResourceAddress), ComponentAddress>,
+    }
+
+    impl {{ project-name | upper_camel_case }}Router {
+        // the pool template must be the address of a published swap template
+        pub fn new(pool_template: TemplateAddress) -> Component<Self> {
+            Component::new(Self {
+                pool_template,
+                pools: BTreeMap::new(),
+            })
+            .with_access_rules(AccessRules::allow_all())
+            .create()
+        }
+
+        // deploys a new pool for the pair A - B and adds it to the registry
+        // the fees is represented as a per-mil quantity (e.g. "1" represents "0.1%")
+        // returns the new pool alongside its admin badge, which is handed over to the caller
+        pub fn create_pool(
+            &mut self,
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+        ) -> (ComponentAddress, Bucket) {
+            let pair = self.check_new_pair(a_addr, b_addr);
+            let (pool, admin_badge): (ComponentAddress, Bucket) =
+                TemplateManager::get(self.pool_template).call("new", args![pair.0, pair.1, fee]);
+            self.register_pool(pair, pool, fee);
+            (pool, admin_badge)
+        }
+
+        // same as "create_pool", but the new pool uses the StableSwap invariant with the given amplification
+        pub fn create_stable_pool(
+            &mut self,
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            amplification: u64,
+        ) -> (ComponentAddress, Bucket) {
+            let pair = self.check_new_pair(a_addr, b_addr);
+            let (pool, admin_badge): (ComponentAddress, Bucket) = TemplateManager::get(self.pool_template)
+                .call("new_stable", args![pair.0, pair.1, fee, amplification]);
+            self.register_pool(pair, pool, fee);
+            (pool, admin_badge)
+        }
+
+        // swaps the input bucket through the pools of each consecutive pair in the path
+        // e.g. the path [A, B, C] swaps A for B in the A - B pool, and then B for C in the B - C pool
+        // the transaction is rejected if the final output is lower than "min_output"
+        pub fn swap_along_path(
+            &self,
+            input_bucket: Bucket,
+            path: Vec<ResourceAddress>,
+            min_output: Amount,
+        ) -> Bucket {
+            assert!(path.len() >= 2, "The path must contain at least two resources");
+            assert_eq!(
+                input_bucket.resource_address(),
+                path[0],
+                "The input bucket resource must be the first resource of the path"
+            );
+
+            let mut bucket = input_bucket;
+            for hop in path.windows(2) {
+                let pool = self.get_existing_pool(hop[0], hop[1]);
+                bucket = ComponentManager::get(pool).call("swap", args![bucket, hop[1]]);
+            }
+
+            assert!(
+                bucket.amount() >= min_output,
+                "Insufficient output amount: {} is lower than the minimum {}",
+                bucket.amount(),
+                min_output
+            );
+
+            bucket
+        }
+
+        pub fn get_pool(&self, a_addr: ResourceAddress, b_addr: ResourceAddress) -> Option<ComponentAddress> {
+            self.pools.get(&Self::pair_key(a_addr, b_addr)).copied()
+        }
+
+        pub fn get_pools(&self) -> BTreeMap<(ResourceAddress, ResourceAddress), ComponentAddress> {
+            self.pools.clone()
+        }
+
+        pub fn pool_template(&self) -> TemplateAddress {
+            self.pool_template
+        }
+
+        fn get_existing_pool(&self, a_addr: ResourceAddress, b_addr: ResourceAddress) -> ComponentAddress {
+            self.get_pool(a_addr, b_addr)
+                .unwrap_or_else(|| panic!("There is no pool for the pair {} - {}", a_addr, b_addr))
+        }
+
+        fn check_new_pair(
+            &self,
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+        ) -> (ResourceAddress, ResourceAddress) {
+            let pair = Self::pair_key(a_addr, b_addr);
+            assert!(
+                !self.pools.contains_key(&pair),
+                "A pool already exists for the pair {} - {}",
+                pair.0,
+                pair.1
+            );
+            pair
+        }
+
+        fn register_pool(&mut self, pair: (ResourceAddress, ResourceAddress), pool: ComponentAddress, fee: u16) {
+            self.pools.insert(pair, pool);
+
+            emit_event(
+                "create_pool",
+                [
+                    ("pool", pool.to_string()),
+                    ("a_resource", pair.0.to_string()),
+                    ("b_resource", pair.1.to_string()),
+                    ("fee", fee.to_string()),
+                ],
+            );
+        }
+
+        fn pair_key(a_addr: ResourceAddress, b_addr: ResourceAddress) -> (ResourceAddress, ResourceAddress) {
+            assert_ne!(a_addr, b_addr, "The resources of the pair must be different");
+            if a_addr < b_addr {
+                (a_addr, b_addr)
+            } else {
+                (b_addr, a_addr)
+            }
+        }
+    }
+}
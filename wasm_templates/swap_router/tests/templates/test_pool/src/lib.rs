@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use tari_template_lib::prelude::*;
+
+// Helper template used by the router tests in place of the swap template, with the same interface for creating pools
+// and swapping. It is a constant product pool without liquidity tokens, or a pool that swaps one for one (minus the
+// fee) when it is created as a stable pool
+#[template]
+mod test_pool {
+    use super::*;
+
+    pub struct TestPool {
+        pools: BTreeMap<ResourceAddress, Vault>,
+        fee: u16,
+        amplification: Option<u64>,
+    }
+
+    impl TestPool {
+        pub fn new(a_addr: ResourceAddress, b_addr: ResourceAddress, fee: u16) -> (Component<Self>, Bucket) {
+            Self::create(a_addr, b_addr, fee, None)
+        }
+
+        pub fn new_stable(
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            amplification: u64,
+        ) -> (Component<Self>, Bucket) {
+            Self::create(a_addr, b_addr, fee, Some(amplification))
+        }
+
+        fn create(
+            a_addr: ResourceAddress,
+            b_addr: ResourceAddress,
+            fee: u16,
+            amplification: Option<u64>,
+        ) -> (Component<Self>, Bucket) {
+            let mut pools = BTreeMap::new();
+            pools.insert(a_addr, Vault::new_empty(a_addr));
+            pools.insert(b_addr, Vault::new_empty(b_addr));
+
+            let admin_badge = ResourceBuilder::non_fungible()
+                .with_token_symbol("admin")
+                .initial_supply([NonFungibleId::from_u32(0)]);
+
+            let component = Component::new(Self {
+                pools,
+                fee,
+                amplification,
+            })
+                .with_access_rules(AccessRules::allow_all())
+                .create();
+            (component, admin_badge)
+        }
+
+        pub fn add_liquidity(&mut self, a_bucket: Bucket, b_bucket: Bucket) {
+            self.get_vault(a_bucket.resource_address()).deposit(a_bucket);
+            self.get_vault(b_bucket.resource_address()).deposit(b_bucket);
+        }
+
+        pub fn swap(&mut self, input_bucket: Bucket, output_resource: ResourceAddress) -> Bucket {
+            let input_resource = input_bucket.resource_address();
+            let input_amount = input_bucket.amount() - input_bucket.amount() * self.fee as u64 / 1000;
+            let input_balance = self.get_vault(input_resource).balance();
+            let output_balance = self.get_vault(output_resource).balance();
+            let output_amount = match self.amplification {
+                Some(_) => input_amount,
+                None => input_amount * output_balance / (input_balance + input_amount),
+            };
+
+            self.get_vault(input_resource).deposit(input_bucket);
+            self.get_vault(output_resource).withdraw(output_amount)
+        }
+
+        pub fn get_balance(&self, resource: ResourceAddress) -> Amount {
+            self.pools[&resource].balance()
+        }
+
+        pub fn get_amplification(&self) -> Option<u64> {
+            self.amplification
+        }
+
+        fn get_vault(&mut self, resource: ResourceAddress) -> &mut Vault {
+            self.pools
+                .get_mut(&resource)
+                .unwrap_or_else(|| panic!("The resource {} is not in the pool", resource))
+        }
+    }
+}
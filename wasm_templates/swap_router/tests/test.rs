@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use tari_template_lib::models::BucketId;
+use tari_template_lib::prelude::{Amount, ComponentAddress, ResourceAddress};
+use tari_template_lib::types::NonFungibleAddress;
+use tari_template_test_tooling::crypto::RistrettoSecretKey;
+use tari_template_test_tooling::support::assert_error::assert_reject_reason;
+use tari_template_test_tooling::transaction::{args, Transaction};
+use tari_template_test_tooling::TemplateTest;
+
+const TEMPLATE_MODULE_NAME: &str = "{{ project-name | upper_camel_case }}Router";
+const POOL_FEE: u16 = 3;
+
+#[test]
+fn it_registers_pools_by_sorted_pair() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        c_resource,
+        ab_pool,
+        bc_pool,
+        ..
+    } = setup();
+
+    // pools are found regardless of the order of the pair
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(router_component, "get_pool", args![a_resource, b_resource])
+            .call_method(router_component, "get_pool", args![b_resource, a_resource])
+            .call_method(router_component, "get_pool", args![a_resource, c_resource])
+            .call_method(router_component, "get_pools", args![])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    let results = &result.finalize.execution_results;
+    assert_eq!(results[0].decode::<Option<ComponentAddress>>().unwrap(), Some(ab_pool));
+    assert_eq!(results[1].decode::<Option<ComponentAddress>>().unwrap(), Some(ab_pool));
+    assert_eq!(results[2].decode::<Option<ComponentAddress>>().unwrap(), None);
+
+    let pools = results[3]
+        .decode::<BTreeMap<(ResourceAddress, ResourceAddress), ComponentAddress>>()
+        .unwrap();
+    assert_eq!(pools.len(), 2);
+    assert!(pools.keys().all(|(a, b)| a < b));
+    assert!(pools.values().any(|pool| *pool == ab_pool));
+    assert!(pools.values().any(|pool| *pool == bc_pool));
+}
+
+#[test]
+fn it_rejects_existing_pairs() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        ..
+    } = setup();
+
+    // the pair is sorted, so the reversed pair is the same pool
+    for (a, b) in [(a_resource, b_resource), (b_resource, a_resource)] {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_method(router_component, "create_pool", args![a, b, POOL_FEE])
+                .build_and_seal(&account_key),
+            vec![account_proof.clone()],
+        );
+        assert_reject_reason(reason, "A pool already exists for the pair");
+    }
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(
+                router_component,
+                "create_pool",
+                args![a_resource, a_resource, POOL_FEE],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    assert_reject_reason(reason, "The resources of the pair must be different");
+}
+
+#[test]
+fn it_swaps_along_path() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        c_resource,
+        ab_pool,
+        bc_pool,
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                router_component,
+                "swap_along_path",
+                args![
+                    Workspace("input"),
+                    vec![a_resource, b_resource, c_resource],
+                    900
+                ],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(account, "balance", args![c_resource])
+            .call_method(ab_pool, "get_balance", args![b_resource])
+            .call_method(bc_pool, "get_balance", args![b_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    let results = &result.finalize.execution_results;
+
+    // 997 A are swapped for (997 * 100_000) / (100_000 + 997) = 987 B
+    // and 985 of those B for (985 * 100_000) / (100_000 + 985) = 975 C
+    assert_eq!(results[5].decode::<Amount>().unwrap(), 1_000_000 - 100_000 + 975);
+    assert_eq!(results[6].decode::<Amount>().unwrap(), 100_000 - 987);
+    assert_eq!(results[7].decode::<Amount>().unwrap(), 100_000 + 987);
+}
+
+#[test]
+fn it_creates_stable_pools_and_swaps_through_them() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        c_resource,
+        ..
+    } = setup();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(
+                router_component,
+                "create_stable_pool",
+                args![c_resource, a_resource, POOL_FEE, 100u64],
+            )
+            .put_last_instruction_output_on_workspace("pool")
+            .call_method(account, "deposit", args![Workspace("pool.1")])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let (ac_pool, _) = result.finalize.execution_results[0]
+        .decode::<(ComponentAddress, BucketId)>()
+        .unwrap();
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 100_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![c_resource, 100_000])
+            .put_last_instruction_output_on_workspace("c")
+            .call_method(ac_pool, "add_liquidity", args![Workspace("a"), Workspace("c")])
+            .call_method(ac_pool, "get_amplification", args![])
+            .call_method(router_component, "get_pool", args![a_resource, c_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let results = &result.finalize.execution_results;
+    assert_eq!(results[5].decode::<Option<u64>>().unwrap(), Some(100));
+    assert_eq!(results[6].decode::<Option<ComponentAddress>>().unwrap(), Some(ac_pool));
+
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                router_component,
+                "swap_along_path",
+                args![Workspace("input"), vec![a_resource, c_resource], 997],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .call_method(account, "balance", args![c_resource])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+
+    // the stable test pool swaps the 997 A left after the fee for the same amount of C
+    let balance = result.finalize.execution_results[5]
+        .decode::<Amount>()
+        .unwrap();
+    assert_eq!(balance, 1_000_000 - 2 * 100_000 + 997);
+}
+
+#[test]
+fn it_rejects_swaps_below_minimum_output() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        c_resource,
+        ..
+    } = setup();
+
+    let reason = test.execute_expect_failure(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 1_000])
+            .put_last_instruction_output_on_workspace("input")
+            .call_method(
+                router_component,
+                "swap_along_path",
+                args![
+                    Workspace("input"),
+                    vec![a_resource, b_resource, c_resource],
+                    976
+                ],
+            )
+            .put_last_instruction_output_on_workspace("output")
+            .call_method(account, "deposit", args![Workspace("output")])
+            .build_and_seal(&account_key),
+        vec![account_proof],
+    );
+    assert_reject_reason(reason, "Insufficient output amount: 975 is lower than the minimum 976");
+}
+
+#[test]
+fn it_rejects_invalid_paths() {
+    let TestSetup {
+        mut test,
+        router_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        c_resource,
+        ..
+    } = setup();
+
+    let invalid_paths = [
+        (vec![a_resource], "The path must contain at least two resources"),
+        (
+            vec![b_resource, c_resource],
+            "The input bucket resource must be the first resource of the path",
+        ),
+        // there is no A - C pool
+        (vec![a_resource, c_resource], "There is no pool for the pair"),
+        (vec![a_resource, b_resource, a_resource, c_resource], "There is no pool for the pair"),
+    ];
+    for (path, expected_reason) in invalid_paths {
+        let reason = test.execute_expect_failure(
+            Transaction::builder_localnet()
+                .call_method(account, "withdraw", args![a_resource, 1_000])
+                .put_last_instruction_output_on_workspace("input")
+                .call_method(
+                    router_component,
+                    "swap_along_path",
+                    args![Workspace("input"), path, 0],
+                )
+                .put_last_instruction_output_on_workspace("output")
+                .call_method(account, "deposit", args![Workspace("output")])
+                .build_and_seal(&account_key),
+            vec![account_proof.clone()],
+        );
+        assert_reject_reason(reason, expected_reason);
+    }
+}
+
+struct TestSetup {
+    test: TemplateTest,
+    router_component: ComponentAddress,
+    account: ComponentAddress,
+    account_proof: NonFungibleAddress,
+    account_key: RistrettoSecretKey,
+    a_resource: ResourceAddress,
+    b_resource: ResourceAddress,
+    c_resource: ResourceAddress,
+    ab_pool: ComponentAddress,
+    bc_pool: ComponentAddress,
+}
+
+// Creates a router with pools for A - B and B - C, with 100_000 tokens of each resource
+fn setup() -> TestSetup {
+    let mut test = TemplateTest::new(
+        ".",
+        [".", "tests/templates/test_tokens", "tests/templates/test_pool"],
+    );
+    let (account, account_proof, account_key) = test.create_funded_account();
+
+    let a_resource = create_token(&mut test, account, &account_proof, &account_key, "A");
+    let b_resource = create_token(&mut test, account, &account_proof, &account_key, "B");
+    let c_resource = create_token(&mut test, account, &account_proof, &account_key, "C");
+
+    let pool_template = test.get_template_address("TestPool");
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(
+                test.get_template_address(TEMPLATE_MODULE_NAME),
+                "new",
+                args![pool_template],
+            )
+            .build_and_seal(&account_key),
+        vec![account_proof.clone()],
+    );
+    let router_component = result.finalize.execution_results[0]
+        .decode::<ComponentAddress>()
+        .unwrap();
+
+    let ab_pool = create_pool(
+        &mut test,
+        router_component,
+        account,
+        &account_proof,
+        &account_key,
+        a_resource, b_resource,
+    );
+    let bc_pool = create_pool(
+        &mut test,
+        router_component,
+        account,
+        &account_proof,
+        &account_key,
+        b_resource, c_resource,
+    );
+
+    TestSetup {
+        test,
+        router_component,
+        account,
+        account_proof,
+        account_key,
+        a_resource,
+        b_resource,
+        c_resource,
+        ab_pool,
+        bc_pool,
+    }
+}
+
+// Creates a pool through the router, keeping its admin badge, and adds 100_000 tokens of each resource
+fn create_pool(
+    test: &mut TemplateTest,
+    router_component: ComponentAddress,
+    account: ComponentAddress,
+    account_proof: &NonFungibleAddress,
+    account_key: &RistrettoSecretKey,
+    a_resource: ResourceAddress,
+    b_resource: ResourceAddress,
+) -> ComponentAddress {
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(
+                router_component,
+                "create_pool",
+                args![a_resource, b_resource, POOL_FEE],
+            )
+            .put_last_instruction_output_on_workspace("pool")
+            .call_method(account, "deposit", args![Workspace("pool.1")])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+    let (pool, _) = result.finalize.execution_results[0]
+        .decode::<(ComponentAddress, BucketId)>()
+        .unwrap();
+
+    test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_method(account, "withdraw", args![a_resource, 100_000])
+            .put_last_instruction_output_on_workspace("a")
+            .call_method(account, "withdraw", args![b_resource, 100_000])
+            .put_last_instruction_output_on_workspace("b")
+            .call_method(pool, "add_liquidity", args![Workspace("a"), Workspace("b")])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+
+    pool
+}
+
+fn create_token(
+    test: &mut TemplateTest,
+    account: ComponentAddress,
+    account_proof: &NonFungibleAddress,
+    account_key: &RistrettoSecretKey,
+    symbol: &str,
+) -> ResourceAddress {
+    let template = test.get_template_address("TestTokens");
+    let result = test.execute_expect_success(
+        Transaction::builder_localnet()
+            .call_function(template, "create", args![symbol, 1_000_000])
+            .put_last_instruction_output_on_workspace("tokens")
+            .call_method(account, "deposit", args![Workspace("tokens")])
+            .build_and_seal(account_key),
+        vec![account_proof.clone()],
+    );
+
+    result
+        .finalize
+        .result
+        .accept()
+        .unwrap()
+        .up_iter()
+        .find_map(|(id, _)| id.as_resource_address())
+        .unwrap()
+}